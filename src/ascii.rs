@@ -66,6 +66,7 @@ impl From<u64> for AsciiInt {
 }
 
 #[cfg(test)]
+#[allow(clippy::partialeq_to_none)]
 mod tests {
     extern crate std;
 
@@ -0,0 +1,234 @@
+use crate::ascii::{CR, LF};
+
+const MALFORMED: &str = "malformed chunked body";
+
+/// Where the decoder is within the chunked framing
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    /// Reading the hex chunk size.  The bool indicates at least one digit has been read.
+    Size(bool),
+    /// Skipping a chunk extension up to the end of the size line
+    Extension,
+    /// Expecting the LF ending the size line
+    SizeLf,
+    /// Within the chunk data
+    Data,
+    /// Expecting the CR following the chunk data
+    DataCr,
+    /// Expecting the LF following the chunk data
+    DataLf,
+    /// At the start of a trailer line, or the empty line ending the body
+    Trailer,
+    /// Skipping a trailer field line
+    TrailerLine,
+    /// Expecting the LF ending a trailer field line
+    TrailerLf,
+    /// Expecting the LF of the empty line ending the body
+    FinalLf,
+    /// The whole body has been decoded
+    Done,
+}
+
+/// What the bytes at the start of the input to `ChunkedDecoder::advance` were
+#[derive(Debug, PartialEq)]
+pub(crate) enum Chunk {
+    /// The number of framing bytes (sizes, extensions, line endings and trailers) consumed
+    Framing(usize),
+    /// The number of body bytes consumed
+    Data(usize),
+}
+
+/// Incremental decoder for a body with `Transfer-Encoding: chunked`.  The input can be provided
+/// in pieces of any size, allowing the same decoder to be used on a body held in a buffer or one
+/// being read from the client.  Chunk extensions and trailer fields are skipped.
+#[derive(Debug)]
+pub(crate) struct ChunkedDecoder {
+    state: State,
+    // bytes of the current chunk yet to be consumed, or the chunk size being read
+    remaining: usize,
+}
+
+impl ChunkedDecoder {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: State::Size(false),
+            remaining: 0,
+        }
+    }
+
+    /// Whether the end of the body has been reached.
+    pub(crate) fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// The number of bytes that can be read from the client without reading beyond the end of
+    /// the body.
+    pub(crate) fn wanted(&self) -> usize {
+        match self.state {
+            State::Data => self.remaining,
+            State::Done => 0,
+            _ => 1,
+        }
+    }
+
+    /// Consume bytes from the start of the input.  Either chunk data, or framing is consumed, but
+    /// never both, so the caller knows which bytes of the input are part of the body.
+    pub(crate) fn advance(&mut self, input: &[u8]) -> Result<Chunk, &'static str> {
+        if self.state == State::Data {
+            let n = input.len().min(self.remaining);
+            self.remaining -= n;
+            if self.remaining == 0 {
+                self.state = State::DataCr;
+            }
+            return Ok(Chunk::Data(n));
+        }
+
+        for (i, b) in input.iter().enumerate() {
+            self.state = match (self.state, *b) {
+                (State::Size(_), b) if b.is_ascii_hexdigit() => {
+                    let digit = (b as char).to_digit(16).unwrap() as usize;
+                    self.remaining = self
+                        .remaining
+                        .checked_mul(16)
+                        .and_then(|n| n.checked_add(digit))
+                        .ok_or(MALFORMED)?;
+                    State::Size(true)
+                }
+                (State::Size(true), b';' | b' ' | b'\t') => State::Extension,
+                (State::Size(true), CR) => State::SizeLf,
+                (State::Extension, CR) => State::SizeLf,
+                (State::Extension, _) => State::Extension,
+                (State::SizeLf, LF) if self.remaining == 0 => State::Trailer,
+                (State::SizeLf, LF) => {
+                    self.state = State::Data;
+                    return Ok(Chunk::Framing(i + 1));
+                }
+                (State::DataCr, CR) => State::DataLf,
+                (State::DataLf, LF) => State::Size(false),
+                (State::Trailer, CR) => State::FinalLf,
+                (State::Trailer, _) => State::TrailerLine,
+                (State::TrailerLine, CR) => State::TrailerLf,
+                (State::TrailerLine, _) => State::TrailerLine,
+                (State::TrailerLf, LF) => State::Trailer,
+                (State::FinalLf, LF) => {
+                    self.state = State::Done;
+                    return Ok(Chunk::Framing(i + 1));
+                }
+                (State::Done, _) => return Ok(Chunk::Framing(i)),
+                _ => return Err(MALFORMED),
+            };
+        }
+
+        Ok(Chunk::Framing(input.len()))
+    }
+
+    /// Decode the chunked body at the start of `buf` in place, moving the chunk data to the start
+    /// of `buf`.  Returns the number of bytes of `buf` consumed, and the length of the decoded
+    /// data, or `None` if `buf` does not hold the whole body.  Nothing is moved unless the whole
+    /// body is present.
+    pub(crate) fn decode_in_place(buf: &mut [u8]) -> Result<Option<(usize, usize)>, &'static str> {
+        // find the end of the body first, so an incomplete body is left as it was received
+        let mut decoder = Self::new();
+        let mut consumed = 0;
+        while !decoder.is_done() {
+            match decoder.advance(&buf[consumed..])? {
+                Chunk::Framing(0) | Chunk::Data(0) => return Ok(None),
+                Chunk::Framing(n) | Chunk::Data(n) => consumed += n,
+            }
+        }
+
+        let mut decoder = Self::new();
+        let mut read = 0;
+        let mut written = 0;
+        while read < consumed {
+            match decoder.advance(&buf[read..consumed])? {
+                Chunk::Framing(n) => read += n,
+                Chunk::Data(n) => {
+                    buf.copy_within(read..read + n, written);
+                    read += n;
+                    written += n;
+                }
+            }
+        }
+
+        Ok(Some((consumed, written)))
+    }
+}
+
+/// The last chunk and the empty line ending a chunked body, without trailer fields
+#[cfg(feature = "json")]
+pub(crate) const LAST_CHUNK: &[u8] = b"0\r\n\r\n";
+
+/// Write `data` to `writer` as a single chunk of a chunked body.  Empty data is not written, as a
+/// chunk of size 0 would end the body.
+#[cfg(feature = "json")]
+pub(crate) async fn write_chunk<W: embedded_io_async::Write>(
+    writer: &mut W,
+    data: &[u8],
+) -> Result<(), W::Error> {
+    if data.is_empty() {
+        return Ok(());
+    }
+
+    let mut size = [0u8; 2 * core::mem::size_of::<usize>()];
+    let mut start = size.len();
+    let mut n = data.len();
+    while n > 0 {
+        start -= 1;
+        size[start] = b"0123456789abcdef"[n % 16];
+        n /= 16;
+    }
+
+    writer.write_all(&size[start..]).await?;
+    writer.write_all(&[CR, LF]).await?;
+    writer.write_all(data).await?;
+    writer.write_all(&[CR, LF]).await
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    #[test]
+    fn test_chunked_decode_in_place() {
+        let mut buf = *b"4\r\nWiki\r\n5;ext=\"x\"\r\npedia\r\n0\r\nExpires: never\r\n\r\nGET /";
+        let (consumed, len) = ChunkedDecoder::decode_in_place(&mut buf).unwrap().unwrap();
+        assert_eq!(&buf[..len], b"Wikipedia");
+        assert_eq!(&buf[consumed..], b"GET /");
+
+        // incomplete bodies are left untouched
+        let mut buf = *b"4\r\nWiki\r\n5\r\npedia\r\n";
+        assert_eq!(ChunkedDecoder::decode_in_place(&mut buf), Ok(None));
+        assert_eq!(&buf, b"4\r\nWiki\r\n5\r\npedia\r\n");
+
+        let mut buf = *b"4\r\nWi";
+        assert_eq!(ChunkedDecoder::decode_in_place(&mut buf), Ok(None));
+
+        for malformed in [&b"x\r\n"[..], b"\r\n", b"4\r\nWikiX\r\n", b"4\nWiki"] {
+            let mut buf = std::vec::Vec::from(malformed);
+            assert!(ChunkedDecoder::decode_in_place(&mut buf).is_err());
+        }
+    }
+
+    #[test]
+    fn test_chunked_decode_byte_at_a_time() {
+        let body = b"4\r\nWiki\r\n5\r\npedia\r\nE\r\n in\r\n\r\nchunks.\r\n0\r\n\r\n";
+        let mut decoder = ChunkedDecoder::new();
+        let mut decoded = std::vec::Vec::new();
+
+        for b in body {
+            assert!(!decoder.is_done());
+            assert!(decoder.wanted() > 0);
+            match decoder.advance(&[*b]).unwrap() {
+                Chunk::Data(1) => decoded.push(*b),
+                Chunk::Framing(1) => {}
+                chunk => panic!("unexpected {chunk:?}"),
+            }
+        }
+
+        assert!(decoder.is_done());
+        assert_eq!(decoded, b"Wikipedia in\r\n\r\nchunks.");
+    }
+}
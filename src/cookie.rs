@@ -0,0 +1,329 @@
+//! Cookies sent by the client in `Cookie` request headers, and `Set-Cookie` response headers built
+//! with `SetCookie`.  Cookies are borrowed from the request, and a `SetCookie` is written straight
+//! to the client, so nothing is allocated.
+//!
+//! ```
+//! use embedded_io_async::{Read, Write};
+//!
+//! use weblite::cookie::{SameSite, SetCookie};
+//! use weblite::request::Request;
+//! use weblite::response::{Responder, StatusCode};
+//! use weblite::server::HandlerError;
+//!
+//! async fn login<C: Read + Write>(
+//!     req: Request<'_>,
+//!     resp: Responder<'_, '_, C>,
+//! ) -> Result<(), HandlerError> {
+//!     if req.cookie("session").is_some() {
+//!         resp.with_status(StatusCode::NoContent).await?.no_body().await?;
+//!         return Ok(());
+//!     }
+//!
+//!     let session = SetCookie::new("session", "31d4d96e407aad42")
+//!         .path("/admin")
+//!         .max_age(3600)
+//!         .secure()
+//!         .http_only()
+//!         .same_site(SameSite::Strict);
+//!
+//!     resp.with_status(StatusCode::NoContent)
+//!         .await?
+//!         .with_header(session.into())
+//!         .await?
+//!         .no_body()
+//!         .await?;
+//!     Ok(())
+//! }
+//! ```
+
+use embedded_io_async::Write;
+
+use crate::WriteError;
+use crate::ascii::AsciiInt;
+use crate::header::HeaderValues;
+
+/// An iterator over the `(name, value)` pairs of the cookies in a request's `Cookie` headers,
+/// created by `Request::cookies`.  Values are as sent, with any surrounding quotes removed.
+#[derive(Clone, Debug)]
+pub struct Cookies<'a> {
+    pairs: HeaderValues<'a, 'static>,
+}
+
+impl<'a> Cookies<'a> {
+    /// The cookies in the `;` separated pairs of `pairs`
+    pub(crate) fn new(pairs: HeaderValues<'a, 'static>) -> Self {
+        Self { pairs }
+    }
+
+    /// The cookies in a single `Cookie` header value, e.g. `session=31d4; theme=dark`
+    pub fn from_header(value: &'a str) -> Self {
+        Self::new(HeaderValues::from_value(value, b';'))
+    }
+}
+
+impl<'a> Iterator for Cookies<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pairs.find_map(|pair| {
+            // a pair without a name is not a cookie
+            let (name, value) = pair.split_once('=')?;
+            let (name, value) = (name.trim(), value.trim());
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            (!name.is_empty()).then_some((name, value))
+        })
+    }
+}
+
+/// The `SameSite` attribute of a cookie, controlling whether it is sent with requests from other
+/// sites.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SameSite {
+    /// Only sent with requests from the site that set it
+    Strict,
+    /// Also sent when navigating to the site from another site
+    Lax,
+    /// Sent with all requests, which browsers only allow for `Secure` cookies
+    None,
+}
+
+impl SameSite {
+    /// String representation of the attribute value
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Strict => "Strict",
+            Self::Lax => "Lax",
+            Self::None => "None",
+        }
+    }
+}
+
+/// A `Set-Cookie` response header, built by adding attributes to a `name=value` pair, and sent
+/// with `with_header(cookie.into())`.  The header is written straight to the client.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SetCookie<'a> {
+    name: &'a str,
+    value: &'a str,
+    path: Option<&'a str>,
+    domain: Option<&'a str>,
+    max_age: Option<u64>,
+    expires: Option<&'a str>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+}
+
+impl<'a> SetCookie<'a> {
+    /// A cookie named `name` with the value `value` and no attributes, which the client keeps
+    /// until it is closed.
+    pub fn new(name: &'a str, value: &'a str) -> Self {
+        Self {
+            name,
+            value,
+            path: None,
+            domain: None,
+            max_age: None,
+            expires: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        }
+    }
+
+    /// A cookie telling the client to delete its cookie named `name`.  The cookie must have the
+    /// same `Path` and `Domain` as the one being deleted.
+    pub fn remove(name: &'a str) -> Self {
+        Self::new(name, "").max_age(0)
+    }
+
+    /// Only send the cookie with requests for paths under `path`
+    pub fn path(mut self, path: &'a str) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    /// Also send the cookie with requests to subdomains of `domain`
+    pub fn domain(mut self, domain: &'a str) -> Self {
+        self.domain = Some(domain);
+        self
+    }
+
+    /// Keep the cookie for `seconds`, or delete it straight away when 0
+    pub fn max_age(mut self, seconds: u64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    /// Keep the cookie until `date`, an HTTP date such as `Wed, 21 Oct 2026 07:28:00 GMT`.  Clients
+    /// use `Max-Age` in preference when both are given.
+    pub fn expires(mut self, date: &'a str) -> Self {
+        self.expires = Some(date);
+        self
+    }
+
+    /// Only send the cookie over HTTPS
+    pub fn secure(mut self) -> Self {
+        self.secure = true;
+        self
+    }
+
+    /// Keep the cookie from scripts running in the page
+    pub fn http_only(mut self) -> Self {
+        self.http_only = true;
+        self
+    }
+
+    /// Control whether the cookie is sent with requests from other sites.  `SameSite::None` also
+    /// makes the cookie `Secure`, as browsers reject it otherwise.
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self.secure |= same_site == SameSite::None;
+        self
+    }
+
+    /// Whether the cookie can be sent without changing the meaning of the header, i.e. the name is
+    /// a token, the value is made of cookie octets, and no attribute contains a `;` or control
+    /// character.
+    pub(crate) fn is_valid(&self) -> bool {
+        let token = |b: u8| b.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?={}".contains(&b);
+        let octet = |b: u8| b.is_ascii_graphic() && !b"\",;\\".contains(&b);
+        let attr = |s: &str| s.bytes().all(|b| b != b';' && !b.is_ascii_control());
+        let value = self
+            .value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(self.value);
+
+        !self.name.is_empty()
+            && self.name.bytes().all(token)
+            && value.bytes().all(octet)
+            && [self.path, self.domain, self.expires]
+                .into_iter()
+                .flatten()
+                .all(attr)
+    }
+
+    /// Write the header value, e.g. `session=31d4; Path=/; HttpOnly`
+    pub(crate) async fn write_value<T: Write>(&self, writer: &mut T) -> Result<(), WriteError> {
+        let max_age = self.max_age.map(AsciiInt::from);
+        let attrs = [
+            ("; Path=", self.path),
+            ("; Domain=", self.domain),
+            ("; Max-Age=", max_age.as_ref().map(AsciiInt::as_str)),
+            ("; Expires=", self.expires),
+            ("; Secure", self.secure.then_some("")),
+            ("; HttpOnly", self.http_only.then_some("")),
+            ("; SameSite=", self.same_site.map(|s| s.as_str())),
+        ];
+
+        writer
+            .write_all(self.name.as_bytes())
+            .await
+            .and(writer.write_all(b"=").await)
+            .and(writer.write_all(self.value.as_bytes()).await)
+            .or(Err(WriteError::NetworkError))?;
+
+        for (attr, value) in attrs {
+            if let Some(value) = value {
+                writer
+                    .write_all(attr.as_bytes())
+                    .await
+                    .and(writer.write_all(value.as_bytes()).await)
+                    .or(Err(WriteError::NetworkError))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+
+    use crate::header::{Headers, REQ_HEAD_COOKIE};
+
+    use super::*;
+
+    fn cookies(headers: &str) -> Cookies<'_> {
+        Cookies::new(HeaderValues::new(
+            Headers::new(headers),
+            REQ_HEAD_COOKIE,
+            b';',
+        ))
+    }
+
+    #[test]
+    fn test_cookies() {
+        let headers = "Host: example.com\r\n\
+            Cookie: session=31d4d96e; theme=\"dark\"\r\n\
+            Accept: */*\r\n\
+            cookie: lang=en;;=orphan; flag; empty=\r\n";
+        assert_eq!(
+            cookies(headers).collect::<Vec<_>>(),
+            [
+                ("session", "31d4d96e"),
+                ("theme", "dark"),
+                ("lang", "en"),
+                ("empty", "")
+            ]
+        );
+        assert_eq!(cookies("Host: example.com\r\n").count(), 0);
+        assert_eq!(
+            Cookies::from_header("a=1; b=2").collect::<Vec<_>>(),
+            [("a", "1"), ("b", "2")]
+        );
+    }
+
+    async fn written(cookie: SetCookie<'_>) -> std::string::String {
+        let mut buf = [0u8; 256];
+        let mut writer = &mut buf[..];
+        assert!(cookie.write_value(&mut writer).await.is_ok());
+        let len = 256 - writer.len();
+        std::string::String::from_utf8(buf[..len].to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_set_cookie() {
+        assert_eq!(written(SetCookie::new("id", "a3fWa")).await, "id=a3fWa");
+        assert_eq!(
+            written(
+                SetCookie::new("id", "a3fWa")
+                    .path("/admin")
+                    .domain("example.com")
+                    .max_age(3600)
+                    .expires("Wed, 21 Oct 2026 07:28:00 GMT")
+                    .http_only()
+                    .same_site(SameSite::Lax)
+            )
+            .await,
+            "id=a3fWa; Path=/admin; Domain=example.com; Max-Age=3600; \
+            Expires=Wed, 21 Oct 2026 07:28:00 GMT; HttpOnly; SameSite=Lax"
+        );
+        assert_eq!(
+            written(SetCookie::new("id", "\"a3fWa\"").same_site(SameSite::None)).await,
+            "id=\"a3fWa\"; Secure; SameSite=None"
+        );
+        assert_eq!(
+            written(SetCookie::remove("id").path("/")).await,
+            "id=; Path=/; Max-Age=0"
+        );
+    }
+
+    #[test]
+    fn test_set_cookie_validity() {
+        assert!(SetCookie::new("id", "a3fWa").is_valid());
+        assert!(SetCookie::new("id", "\"a3fWa\"").is_valid());
+        assert!(SetCookie::remove("id").is_valid());
+        assert!(!SetCookie::new("", "a").is_valid());
+        assert!(!SetCookie::new("my id", "a").is_valid());
+        assert!(!SetCookie::new("id", "a b").is_valid());
+        assert!(!SetCookie::new("id", "a;b").is_valid());
+        assert!(!SetCookie::new("id", "a\r\nX-Injected: 1").is_valid());
+        assert!(!SetCookie::new("id", "a").path("/\r\n").is_valid());
+        assert!(!SetCookie::new("id", "a").domain("a.com; Secure").is_valid());
+    }
+}
@@ -0,0 +1,395 @@
+//! Bodies submitted by HTML forms (`application/x-www-form-urlencoded`), such as
+//! `name=my+device&interval=30`.  These are read like query strings with `Request::form`, or with
+//! the `serde` feature, deserialized into a type deriving `Deserialize` with `Request::form_as`.
+//!
+//! ```
+//! # #[cfg(feature = "serde")]
+//! # {
+//! use serde::Deserialize;
+//! use weblite::form::{FormError, from_form};
+//! use weblite::query::Query;
+//!
+//! #[derive(Deserialize)]
+//! struct Settings<'a> {
+//!     name: &'a str,
+//!     interval: u32,
+//!     // an unchecked checkbox is left out of the form
+//!     #[serde(default)]
+//!     enabled: bool,
+//!     note: Option<&'a str>,
+//! }
+//!
+//! // decoded values are written to the buffer, so no allocation is needed
+//! let mut buf = [0u8; 32];
+//! let settings: Settings = from_form(Query::new("name=my+device&interval=30&enabled=on"), &mut buf)?;
+//!
+//! assert_eq!(settings.name, "my device");
+//! assert_eq!(settings.interval, 30);
+//! assert!(settings.enabled);
+//! assert!(settings.note.is_none());
+//! # }
+//! # Ok::<(), weblite::form::FormError>(())
+//! ```
+
+use core::fmt;
+
+use crate::query::DecodeError;
+#[cfg(feature = "serde")]
+pub use de::from_form;
+
+/// The media type of bodies submitted by HTML forms
+pub const FORM_URLENCODED: &str = "application/x-www-form-urlencoded";
+
+/// Errors reading a form from a request body
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FormError {
+    /// The request's Content-Type is not `application/x-www-form-urlencoded`
+    UnsupportedContentType,
+    /// The body did not fit in the server's buffer, and must be read with a `BodyReader`
+    BodyNotBuffered,
+    /// The body is not valid utf8, or a value could not be decoded
+    DecodeError(DecodeError),
+    /// The form does not match the type it is deserialized into, e.g. a required field is missing
+    #[cfg(feature = "serde")]
+    Mismatch,
+}
+
+impl From<DecodeError> for FormError {
+    fn from(value: DecodeError) -> Self {
+        Self::DecodeError(value)
+    }
+}
+
+impl fmt::Display for FormError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Self::UnsupportedContentType => "request body is not a form",
+            Self::BodyNotBuffered => "form body did not fit in the buffer",
+            Self::DecodeError(DecodeError::BufferTooSmall) => "decoded form does not fit in buffer",
+            Self::DecodeError(DecodeError::InvalidValue) => "invalid form value",
+            Self::DecodeError(DecodeError::Malformed) => "malformed form encoding",
+            #[cfg(feature = "serde")]
+            Self::Mismatch => "form does not match the expected fields",
+        };
+        f.write_str(reason)
+    }
+}
+
+/// Whether a Content-Type header value is that of a form, ignoring any parameters such as the
+/// charset.
+pub(crate) fn is_form(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case(FORM_URLENCODED))
+}
+
+#[cfg(feature = "serde")]
+mod de {
+    use serde::de::value::BorrowedStrDeserializer;
+    use serde::de::{self, Deserialize, DeserializeSeed, MapAccess, Visitor};
+    use serde::forward_to_deserialize_any;
+
+    use super::FormError;
+    use crate::query::{DecodeError, Pairs, Query, decode, decodes_to};
+
+    impl de::StdError for FormError {}
+
+    impl de::Error for FormError {
+        // without an allocator there is nowhere to keep serde's message
+        fn custom<T: core::fmt::Display>(_msg: T) -> Self {
+            Self::Mismatch
+        }
+    }
+
+    /// Deserialize the fields of a form into a `T`.  Keys and values that are percent-encoded are
+    /// decoded into `buf`, which must be large enough to hold them; the others are borrowed from
+    /// the form.
+    ///
+    /// Values are parsed into the type of their field.  A `bool` field is true for `true`, `on`
+    /// (sent by a checked checkbox) or `1`, and an `Option` field is `None` when the form does not
+    /// have it.  When a key is repeated the first value is used, and the others are ignored.
+    ///
+    /// A browser leaves an unchecked checkbox out of the form altogether, so a `bool` field for one
+    /// needs `#[serde(default)]` to be false when it is missing, rather than failing with
+    /// `FormError::Mismatch` as any other missing field does.
+    pub fn from_form<'de, T: Deserialize<'de>>(
+        form: Query<'de>,
+        buf: &'de mut [u8],
+    ) -> Result<T, FormError> {
+        T::deserialize(&mut FormDeserializer {
+            form,
+            pairs: form.iter(),
+            taken: 0,
+            value: "",
+            buf,
+        })
+    }
+
+    struct FormDeserializer<'de> {
+        form: Query<'de>,
+        pairs: Pairs<'de>,
+        // the number of pairs taken from `pairs`
+        taken: usize,
+        // the value of the key most recently returned
+        value: &'de str,
+        buf: &'de mut [u8],
+    }
+
+    impl<'de> FormDeserializer<'de> {
+        /// Decode `encoded`, taking the space it needs from the front of the buffer
+        fn decode(&mut self, encoded: &'de str) -> Result<&'de str, FormError> {
+            if !encoded.contains(['%', '+']) {
+                return Ok(encoded);
+            }
+            let len = decode(encoded, self.buf)?.len();
+            let (decoded, rest) = core::mem::take(&mut self.buf).split_at_mut(len);
+            self.buf = rest;
+            let decoded: &'de [u8] = decoded;
+            // decode has validated the utf8
+            Ok(str::from_utf8(decoded).unwrap())
+        }
+    }
+
+    impl<'de> de::Deserializer<'de> for &mut FormDeserializer<'de> {
+        type Error = FormError;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, FormError> {
+            visitor.visit_map(self)
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map struct enum identifier ignored_any
+        }
+    }
+
+    impl<'de> MapAccess<'de> for FormDeserializer<'de> {
+        type Error = FormError;
+
+        fn next_key_seed<K: DeserializeSeed<'de>>(
+            &mut self,
+            seed: K,
+        ) -> Result<Option<K::Value>, FormError> {
+            loop {
+                let Some((key, value)) = self.pairs.next() else {
+                    return Ok(None);
+                };
+                self.taken += 1;
+                let key = self.decode(key)?;
+                // only the first value of a repeated key is used
+                let repeated = self
+                    .form
+                    .iter()
+                    .take(self.taken - 1)
+                    .any(|(earlier, _)| decodes_to(earlier, key));
+                if !repeated {
+                    self.value = value;
+                    return seed
+                        .deserialize(BorrowedStrDeserializer::new(key))
+                        .map(Some);
+                }
+            }
+        }
+
+        fn next_value_seed<V: DeserializeSeed<'de>>(
+            &mut self,
+            seed: V,
+        ) -> Result<V::Value, FormError> {
+            let value = self.decode(self.value)?;
+            seed.deserialize(ValueDeserializer(value))
+        }
+    }
+
+    /// Deserializes a single decoded value, parsing it into the type requested
+    struct ValueDeserializer<'de>(&'de str);
+
+    macro_rules! parse_value {
+        ($($method:ident => $visit:ident,)*) => {
+            $(
+                fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, FormError> {
+                    match self.0.parse() {
+                        Ok(value) => visitor.$visit(value),
+                        Err(_) => Err(FormError::DecodeError(DecodeError::InvalidValue)),
+                    }
+                }
+            )*
+        };
+    }
+
+    impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+        type Error = FormError;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, FormError> {
+            visitor.visit_borrowed_str(self.0)
+        }
+
+        fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, FormError> {
+            match self.0 {
+                "true" | "on" | "1" => visitor.visit_bool(true),
+                "false" | "off" | "0" | "" => visitor.visit_bool(false),
+                _ => Err(FormError::DecodeError(DecodeError::InvalidValue)),
+            }
+        }
+
+        parse_value! {
+            deserialize_i8 => visit_i8,
+            deserialize_i16 => visit_i16,
+            deserialize_i32 => visit_i32,
+            deserialize_i64 => visit_i64,
+            deserialize_i128 => visit_i128,
+            deserialize_u8 => visit_u8,
+            deserialize_u16 => visit_u16,
+            deserialize_u32 => visit_u32,
+            deserialize_u64 => visit_u64,
+            deserialize_u128 => visit_u128,
+            deserialize_f32 => visit_f32,
+            deserialize_f64 => visit_f64,
+            deserialize_char => visit_char,
+        }
+
+        fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, FormError> {
+            visitor.visit_some(self)
+        }
+
+        fn deserialize_newtype_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, FormError> {
+            visitor.visit_newtype_struct(self)
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            name: &'static str,
+            variants: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, FormError> {
+            BorrowedStrDeserializer::new(self.0).deserialize_enum(name, variants, visitor)
+        }
+
+        forward_to_deserialize_any! {
+            str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+            identifier ignored_any
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use serde::Deserialize;
+
+        use super::*;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(rename_all = "lowercase")]
+        enum Mode {
+            Station,
+            Access,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Settings<'a> {
+            ssid: &'a str,
+            password: &'a str,
+            channel: u8,
+            mode: Mode,
+            dhcp: bool,
+            ipv6: bool,
+            hostname: Option<&'a str>,
+        }
+
+        #[test]
+        fn test_from_form() {
+            let mut buf = [0u8; 16];
+            let form = Query::new(
+                "ssid=My+Net&password=p%40ss%26word&channel=6&mode=access&dhcp=on&ipv6=0&extra=1",
+            );
+            assert_eq!(
+                from_form::<Settings>(form, &mut buf),
+                Ok(Settings {
+                    ssid: "My Net",
+                    password: "p@ss&word",
+                    channel: 6,
+                    mode: Mode::Access,
+                    dhcp: true,
+                    ipv6: false,
+                    hostname: None,
+                })
+            );
+
+            let mut buf = [0u8; 16];
+            let form = Query::new("ssid=a&password=b&channel=600&mode=station&dhcp=on&ipv6=0");
+            assert_eq!(
+                from_form::<Settings>(form, &mut buf),
+                Err(FormError::DecodeError(DecodeError::InvalidValue))
+            );
+
+            let mut buf = [0u8; 16];
+            let form = Query::new("ssid=a&channel=6&mode=station&dhcp=on&ipv6=0");
+            assert_eq!(
+                from_form::<Settings>(form, &mut buf),
+                Err(FormError::Mismatch)
+            );
+
+            // the first of a repeated key is used, however it is encoded
+            let mut buf = [0u8; 16];
+            let form = Query::new(
+                "ssid=a&password=b&channel=6&ch%61nnel=7&mode=station&dhcp=on&ipv6=0&channel=x",
+            );
+            assert_eq!(from_form::<Settings>(form, &mut buf).unwrap().channel, 6);
+
+            let mut buf = [0u8; 4];
+            let form = Query::new("ssid=a+long+name&password=b&channel=6");
+            assert_eq!(
+                from_form::<Settings>(form, &mut buf),
+                Err(FormError::DecodeError(DecodeError::BufferTooSmall))
+            );
+        }
+
+        #[test]
+        fn test_from_form_repeated_key() {
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Field {
+                a: u8,
+            }
+
+            let mut buf = [0u8; 0];
+            assert_eq!(
+                from_form::<Field>(Query::new("a=1&a=2"), &mut buf),
+                Ok(Field { a: 1 })
+            );
+        }
+
+        #[test]
+        fn test_from_form_unchecked_checkbox() {
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Checkbox {
+                #[serde(default)]
+                enabled: bool,
+            }
+
+            let mut buf = [0u8; 0];
+            assert_eq!(
+                from_form::<Checkbox>(Query::new(""), &mut buf),
+                Ok(Checkbox { enabled: false })
+            );
+            assert_eq!(
+                from_form::<Checkbox>(Query::new("enabled=on"), &mut buf),
+                Ok(Checkbox { enabled: true })
+            );
+
+            // without a default a missing bool is like any other missing field
+            let mut buf = [0u8; 0];
+            assert_eq!(
+                from_form::<Settings>(
+                    Query::new("ssid=a&password=b&channel=6&mode=station&dhcp=on"),
+                    &mut buf
+                ),
+                Err(FormError::Mismatch)
+            );
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use embedded_io_async::Write;
 
 use crate::ascii::{AsciiInt, CR, LF, atoi};
+use crate::cookie::SetCookie;
+use crate::request::Method;
 use crate::{HttpWrite, WriteError};
 
 /// Host
@@ -43,6 +45,12 @@ pub const REQ_HEAD_CONTENT_LOCATION: &str = "Content-Location";
 pub const REQ_HEAD_CONTENT_LANGUAGE: &str = "Content-Language";
 /// ETag
 pub const REQ_HEAD_ETAG: &str = "ETag";
+/// Expect
+pub const REQ_HEAD_EXPECT: &str = "Expect";
+/// Transfer-Encoding
+pub const REQ_HEAD_TRANSFER_ENCODING: &str = "Transfer-Encoding";
+/// Cookie
+pub const REQ_HEAD_COOKIE: &str = "Cookie";
 
 #[allow(missing_docs)]
 #[non_exhaustive]
@@ -68,6 +76,9 @@ pub enum RequestHeader<'a> {
     ContentLocation(&'a str),
     ContentLanguage(&'a str),
     ETag(&'a str),
+    Expect(&'a str),
+    TransferEncoding(&'a str),
+    Cookie(&'a str),
     Other(&'a str, &'a str),
 }
 
@@ -132,6 +143,15 @@ impl<'a> TryFrom<(&'a str, &'a str)> for RequestHeader<'a> {
                 Ok(RequestHeader::ContentLanguage(value.1))
             }
             _ if value.0.eq_ignore_ascii_case(REQ_HEAD_ETAG) => Ok(RequestHeader::ETag(value.1)),
+            _ if value.0.eq_ignore_ascii_case(REQ_HEAD_EXPECT) => {
+                Ok(RequestHeader::Expect(value.1))
+            }
+            _ if value.0.eq_ignore_ascii_case(REQ_HEAD_TRANSFER_ENCODING) => {
+                Ok(RequestHeader::TransferEncoding(value.1))
+            }
+            _ if value.0.eq_ignore_ascii_case(REQ_HEAD_COOKIE) => {
+                Ok(RequestHeader::Cookie(value.1))
+            }
 
             _ if value.0.eq_ignore_ascii_case(REQ_HEAD_CONTENT_LENGTH) => {
                 Ok(RequestHeader::ContentLength(
@@ -143,8 +163,161 @@ impl<'a> TryFrom<(&'a str, &'a str)> for RequestHeader<'a> {
     }
 }
 
+impl<'a> RequestHeader<'a> {
+    /// The name of the header, e.g. `Content-Type`
+    pub fn name(&self) -> &'a str {
+        match self {
+            Self::Host(_) => REQ_HEAD_HOST,
+            Self::UserAgent(_) => REQ_HEAD_USER_AGENT,
+            Self::Upgrade(_) => REQ_HEAD_UPGRADE,
+            Self::SecWebSocketKey(_) => REQ_HEAD_SEC_WEBSOCKET_KEY,
+            Self::Accept(_) => REQ_HEAD_ACCEPT,
+            Self::AcceptLanguage(_) => REQ_HEAD_ACCEPT_LANGUAGE,
+            Self::AcceptEncoding(_) => REQ_HEAD_ACCEPT_ENCODING,
+            Self::Referer(_) => REQ_HEAD_REFERER,
+            Self::Connection(_) => REQ_HEAD_CONNECTION,
+            Self::UpgradeInsecureRequests(_) => REQ_HEAD_UPGRADE_INSECURE_REQUESTS,
+            Self::IfModifiedSince(_) => REQ_HEAD_IF_MODIFIED_SINCE,
+            Self::IfNoneMatch(_) => REQ_HEAD_IF_NONE_MATCH,
+            Self::CacheControl(_) => REQ_HEAD_CACHE_CONTROL,
+            Self::ContentLength(_) => REQ_HEAD_CONTENT_LENGTH,
+            Self::ContentRange(_) => REQ_HEAD_CONTENT_RANGE,
+            Self::ContentType(_) => REQ_HEAD_CONTENT_TYPE,
+            Self::ContentEncoding(_) => REQ_HEAD_CONTENT_ENCODING,
+            Self::ContentLocation(_) => REQ_HEAD_CONTENT_LOCATION,
+            Self::ContentLanguage(_) => REQ_HEAD_CONTENT_LANGUAGE,
+            Self::ETag(_) => REQ_HEAD_ETAG,
+            Self::Expect(_) => REQ_HEAD_EXPECT,
+            Self::TransferEncoding(_) => REQ_HEAD_TRANSFER_ENCODING,
+            Self::Cookie(_) => REQ_HEAD_COOKIE,
+            Self::Other(name, _) => name,
+        }
+    }
+}
+
+/// An iterator over the `(name, value)` pairs of a request's headers in the order they were sent,
+/// including repeated headers, created by `Request::headers`.  Names and values are trimmed of
+/// whitespace.  A pair converts to a `RequestHeader` with `RequestHeader::try_from`.
+#[derive(Clone, Debug, Default)]
+pub struct Headers<'a> {
+    // the header lines not yet iterated, each ending in CRLF
+    rest: &'a str,
+}
+
+impl<'a> Headers<'a> {
+    /// The headers in `block`, holding lines of `name: value` each ending in CRLF
+    pub(crate) fn new(block: &'a str) -> Self {
+        Self { rest: block }
+    }
+}
+
+impl<'a> Iterator for Headers<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.rest.is_empty() {
+            let (line, rest) = self.rest.split_once("\r\n").unwrap_or((self.rest, ""));
+            self.rest = rest;
+            if let Some((name, value)) = line.split_once(':') {
+                return Some((name.trim(), value.trim()));
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over the values of every header with a given name, splitting list values into
+/// their elements, created by `Request::get_all`.
+#[derive(Clone, Debug)]
+pub struct HeaderValues<'a, 'h> {
+    headers: Headers<'a>,
+    name: &'h str,
+    separator: u8,
+    // the part of the current header's value not yet iterated
+    rest: Option<&'a str>,
+}
+
+impl<'a, 'h> HeaderValues<'a, 'h> {
+    /// The values of the headers in `headers` named `name`, split at `separator`
+    pub(crate) fn new(headers: Headers<'a>, name: &'h str, separator: u8) -> Self {
+        Self {
+            headers,
+            name,
+            separator,
+            rest: None,
+        }
+    }
+
+    /// The elements of the single header value `value`, split at `separator`
+    pub(crate) fn from_value(value: &'a str, separator: u8) -> Self {
+        Self {
+            headers: Headers::default(),
+            name: "",
+            separator,
+            rest: Some(value),
+        }
+    }
+}
+
+impl<'a> Iterator for HeaderValues<'a, '_> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let rest = match self.rest {
+                Some(rest) => rest,
+                None => {
+                    let name = self.name;
+                    let (_, value) = self
+                        .headers
+                        .find(|(header, _)| header.eq_ignore_ascii_case(name))?;
+                    value
+                }
+            };
+
+            // separators within a quoted string, e.g. `"a,b"`, do not end an element
+            let (mut quoted, mut escaped) = (false, false);
+            let end = rest.bytes().position(|b| {
+                match b {
+                    _ if escaped => escaped = false,
+                    b'\\' if quoted => escaped = true,
+                    b'"' => quoted = !quoted,
+                    _ => return !quoted && b == self.separator,
+                }
+                false
+            });
+            let element = match end {
+                Some(i) => {
+                    self.rest = Some(&rest[i + 1..]);
+                    &rest[..i]
+                }
+                None => {
+                    self.rest = None;
+                    rest
+                }
+            };
+
+            // empty elements (`a, , b`) are skipped
+            let element = element.trim();
+            if !element.is_empty() {
+                return Some(element);
+            }
+        }
+    }
+}
+
+/// Returns true if the comma separated header value contains the token (case insensitive) e.g.
+/// `has_token("keep-alive, Upgrade", "upgrade")`
+pub(crate) fn has_token(value: &str, token: &str) -> bool {
+    value
+        .split(',')
+        .any(|t| t.trim().eq_ignore_ascii_case(token))
+}
+
 /// Access-Control-Allow-Origin
 pub const RESP_HEAD_ACCESS_CONTROL_ALLOW_ORIGIN: &str = "Access-Control-Allow-Origin";
+/// Allow
+pub const RESP_HEAD_ALLOW: &str = "Allow";
 /// Connection
 pub const RESP_HEAD_CONNECTION: &str = "Connection";
 /// Date
@@ -175,6 +348,8 @@ pub const RESP_HEAD_CONTENT_LOCATION: &str = "Content-Location";
 pub const RESP_HEAD_CONTENT_LANGUAGE: &str = "Content-Language";
 /// ETag
 pub const RESP_HEAD_ETAG: &str = "ETag";
+/// Link
+pub const RESP_HEAD_LINK: &str = "Link";
 /// Sec-WebSocket-Accept
 pub const RESP_HEAD_SEC_WEBSOCKET_ACCEPT: &str = "Sec-WebSocket-Accept";
 
@@ -183,6 +358,7 @@ pub const RESP_HEAD_SEC_WEBSOCKET_ACCEPT: &str = "Sec-WebSocket-Accept";
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ResponseHeader<'a> {
     AccessControlAllowOrigin(&'a str),
+    Allow(&'a [Method]),
     Connection(&'a str),
     Date(&'a str),
     KeepAlive(&'a str),
@@ -198,10 +374,19 @@ pub enum ResponseHeader<'a> {
     ContentLocation(&'a str),
     ContentLanguage(&'a str),
     ETag(&'a str),
+    Link(&'a str),
     SecWebSocketAccept([u8; 28]),
+    /// A Set-Cookie header built with `cookie::SetCookie`
+    Cookie(SetCookie<'a>),
     Other(&'a str, &'a str),
 }
 
+impl<'a> From<SetCookie<'a>> for ResponseHeader<'a> {
+    fn from(value: SetCookie<'a>) -> Self {
+        Self::Cookie(value)
+    }
+}
+
 impl<'a> HttpWrite for ResponseHeader<'a> {
     async fn write<T: Write>(self, writer: &mut T) -> Result<(), WriteError> {
         let len: AsciiInt;
@@ -215,6 +400,27 @@ impl<'a> HttpWrite for ResponseHeader<'a> {
                     .or(Err(WriteError::NetworkError))?;
                 s
             }
+            Self::Allow(methods) => {
+                writer
+                    .write_all(RESP_HEAD_ALLOW.as_bytes())
+                    .await
+                    .and(writer.write_all(b":").await)
+                    .or(Err(WriteError::NetworkError))?;
+
+                for (i, method) in methods.iter().enumerate() {
+                    let sep: &[u8] = if i == 0 { b" " } else { b", " };
+                    writer
+                        .write_all(sep)
+                        .await
+                        .and(writer.write_all(method.as_str().as_bytes()).await)
+                        .or(Err(WriteError::NetworkError))?;
+                }
+
+                return writer
+                    .write_all(&[CR, LF])
+                    .await
+                    .or(Err(WriteError::NetworkError));
+            }
             Self::Connection(s) => {
                 writer
                     .write_all(RESP_HEAD_CONNECTION.as_bytes())
@@ -325,6 +531,13 @@ impl<'a> HttpWrite for ResponseHeader<'a> {
                     .or(Err(WriteError::NetworkError))?;
                 s
             }
+            Self::Link(s) => {
+                writer
+                    .write_all(RESP_HEAD_LINK.as_bytes())
+                    .await
+                    .or(Err(WriteError::NetworkError))?;
+                s
+            }
             Self::SecWebSocketAccept(s) => {
                 writer
                     .write_all(RESP_HEAD_SEC_WEBSOCKET_ACCEPT.as_bytes())
@@ -333,6 +546,19 @@ impl<'a> HttpWrite for ResponseHeader<'a> {
                 ws_accept = s;
                 str::from_utf8(&ws_accept).unwrap()
             }
+            Self::Cookie(cookie) => {
+                writer
+                    .write_all(RESP_HEAD_SET_COOKIE.as_bytes())
+                    .await
+                    .and(writer.write_all(b": ").await)
+                    .or(Err(WriteError::NetworkError))?;
+                cookie.write_value(writer).await?;
+
+                return writer
+                    .write_all(&[CR, LF])
+                    .await
+                    .or(Err(WriteError::NetworkError));
+            }
             Self::Other(k, v) => {
                 writer
                     .write_all(k.as_bytes())
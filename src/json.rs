@@ -0,0 +1,169 @@
+//! JSON request and response bodies, and websocket messages, using `serde-json-core` so no
+//! allocation is needed.  Available with the `json` feature.
+//!
+//! ```
+//! use embedded_io_async::{Read, Write};
+//! use serde::Deserialize;
+//!
+//! use weblite::request::Request;
+//! use weblite::response::{Responder, StatusCode};
+//! use weblite::server::HandlerError;
+//!
+//! #[derive(Deserialize)]
+//! struct Led<'a> {
+//!     colour: &'a str,
+//!     brightness: u8,
+//! }
+//!
+//! async fn set_led<C: Read + Write>(
+//!     req: Request<'_>,
+//!     resp: Responder<'_, '_, C>,
+//! ) -> Result<(), HandlerError> {
+//!     // an error is answered with a 415 Unsupported Media Type, or 400 Bad Request for invalid
+//!     // JSON
+//!     let led: Led = req.json()?;
+//!     // set the LED to led.colour at led.brightness
+//!     resp.with_status(StatusCode::NoContent).await?.no_body().await?;
+//!     Ok(())
+//! }
+//! ```
+
+use embedded_io_async::Read;
+use serde::Deserialize;
+
+use crate::response::StatusCode;
+
+/// The media type of JSON bodies
+pub const APPLICATION_JSON: &str = "application/json";
+
+/// Errors reading JSON from a request body
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum JsonError {
+    /// The request's Content-Type is not `application/json`
+    UnsupportedContentType,
+    /// The body did not fit in the server's buffer, and must be read with `Request::json_from`
+    BodyNotBuffered,
+    /// The body does not fit in the buffer provided
+    BodyTooLarge,
+    /// Reading the body from the client failed
+    ReadError,
+    /// The body is not valid JSON, or does not match the type it is deserialized into
+    InvalidJson(#[cfg_attr(feature = "defmt", defmt(Debug2Format))] serde_json_core::de::Error),
+}
+
+impl JsonError {
+    /// The status of the response appropriate to the error: 415 Unsupported Media Type for a body
+    /// that is not JSON, 413 Content Too Large for one too large to deserialize, and otherwise
+    /// 400 Bad Request.  An error returned from a handler with `?` becomes a
+    /// `HandlerError::Rejected` with this status, which the server responds with.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::UnsupportedContentType => StatusCode::UnsupportedMediaType,
+            Self::BodyNotBuffered | Self::BodyTooLarge => StatusCode::ContentTooLarge,
+            Self::ReadError | Self::InvalidJson(_) => StatusCode::BadRequest,
+        }
+    }
+}
+
+impl From<serde_json_core::de::Error> for JsonError {
+    fn from(value: serde_json_core::de::Error) -> Self {
+        Self::InvalidJson(value)
+    }
+}
+
+/// Whether a Content-Type header value is that of JSON, either `application/json` or a media type
+/// with the `+json` suffix, ignoring any parameters such as the charset.
+pub(crate) fn is_json(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .map(str::trim)
+        .is_some_and(|media_type| {
+            media_type.eq_ignore_ascii_case(APPLICATION_JSON)
+                || media_type
+                    .get(media_type.len().saturating_sub(5)..)
+                    .is_some_and(|suffix| suffix.eq_ignore_ascii_case("+json"))
+        })
+}
+
+/// Deserialize a `T` from a JSON body.  Strings borrowed from the body cannot contain escape
+/// sequences.  Only whitespace may follow the value, which `serde-json-core` checks.
+pub(crate) fn from_body<'b, T: Deserialize<'b>>(body: &'b [u8]) -> Result<T, JsonError> {
+    Ok(serde_json_core::from_slice(body)?.0)
+}
+
+/// Read the whole of a body from `reader` into `buf`, and deserialize a `T` from it.
+pub(crate) async fn from_reader<'b, T: Deserialize<'b>, R: Read>(
+    mut reader: R,
+    buf: &'b mut [u8],
+) -> Result<T, JsonError> {
+    let mut len = 0;
+    loop {
+        if len == buf.len() {
+            // the body must end here to fit
+            return match reader.read(&mut [0u8]).await {
+                Ok(0) => break,
+                Ok(_) => Err(JsonError::BodyTooLarge),
+                Err(_) => Err(JsonError::ReadError),
+            };
+        }
+        match reader.read(&mut buf[len..]).await {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(_) => return Err(JsonError::ReadError),
+        }
+    }
+
+    let buf: &'b [u8] = buf;
+    from_body(&buf[..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Reading<'a> {
+        sensor: &'a str,
+        value: f32,
+    }
+
+    #[test]
+    fn test_json_content_type() {
+        assert!(is_json("application/json"));
+        assert!(is_json("Application/JSON; charset=utf-8"));
+        assert!(is_json("application/merge-patch+json"));
+        assert!(!is_json("text/plain"));
+        assert!(!is_json("json"));
+    }
+
+    #[tokio::test]
+    async fn test_json_from_reader() {
+        let body = br#"{"sensor": "t1", "value": 21.5}"#;
+
+        let mut buf = [0u8; 64];
+        assert_eq!(
+            from_reader::<Reading, _>(&body[..], &mut buf).await,
+            Ok(Reading {
+                sensor: "t1",
+                value: 21.5
+            })
+        );
+
+        // exactly filling the buffer is fine, but not overflowing it
+        let mut buf = [0u8; 31];
+        assert!(from_reader::<Reading, _>(&body[..], &mut buf).await.is_ok());
+        let mut buf = [0u8; 30];
+        assert_eq!(
+            from_reader::<Reading, _>(&body[..], &mut buf).await,
+            Err(JsonError::BodyTooLarge)
+        );
+
+        let mut buf = [0u8; 64];
+        assert!(matches!(
+            from_reader::<Reading, _>(&br#"{"sensor": "t1"}"#[..], &mut buf).await,
+            Err(JsonError::InvalidJson(_))
+        ));
+    }
+}
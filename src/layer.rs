@@ -0,0 +1,435 @@
+//! Middleware that wraps a `RequestHandler` to deal with concerns common to every request, such as
+//! logging, authentication or adding headers.  Layers are composed at compile time with
+//! `RequestHandler::layer`, so no allocation is required.
+//!
+//! ```
+//! use embedded_io_async::{Read, Write};
+//!
+//! use weblite::header::{RequestHeader, ResponseHeader};
+//! use weblite::layer::Layer;
+//! use weblite::request::Request;
+//! use weblite::response::{Responder, StatusCode};
+//! use weblite::server::{HandlerError, RequestHandler, Server};
+//! use weblite::websocket::Websocket;
+//!
+//! /// Refuses requests without the expected credentials
+//! struct Auth {}
+//!
+//! impl Layer for Auth {
+//!     async fn handle_request<'client, 'buff, C: Read + Write + 'client, H: RequestHandler>(
+//!         &self,
+//!         req: Request<'buff>,
+//!         resp: Responder<'buff, 'client, C>,
+//!         next: &H,
+//!     ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+//!         match req.get_header(RequestHeader::Other("Authorization", "")) {
+//!             Some(RequestHeader::Other(_, "Bearer secret")) => next.handle_request(req, resp).await,
+//!             _ => {
+//!                 resp.with_status(StatusCode::Other(401)).await?.no_body().await?;
+//!                 Ok(None)
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! /// Adds CORS headers to every response, including preflight and error responses
+//! struct Cors {}
+//!
+//! const CORS: &[ResponseHeader] = &[ResponseHeader::AccessControlAllowOrigin("*")];
+//!
+//! impl Layer for Cors {
+//!     async fn handle_request<'client, 'buff, C: Read + Write + 'client, H: RequestHandler>(
+//!         &self,
+//!         req: Request<'buff>,
+//!         resp: Responder<'buff, 'client, C>,
+//!         next: &H,
+//!     ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+//!         next.handle_request(req, resp.with_default_headers(CORS)?).await
+//!     }
+//!
+//!     async fn handle_error<'client, 'buff, C: Read + Write + 'client, H: RequestHandler>(
+//!         &self,
+//!         status: StatusCode,
+//!         resp: Responder<'buff, 'client, C>,
+//!         next: &H,
+//!     ) -> Result<(), HandlerError> {
+//!         next.handle_error(status, resp.with_default_headers(CORS)?).await
+//!     }
+//! }
+//!
+//! struct Handler {}
+//!
+//! impl RequestHandler for Handler {
+//! #    async fn handle_request<'client, 'buff, C: Read + Write + 'client>(
+//! #        &self,
+//! #        req: Request<'buff>,
+//! #        resp: Responder<'buff, 'client, C>
+//! #    ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+//! #        Err(HandlerError::CustomError("not implemented"))
+//! #    }
+//! }
+//!
+//! // Cors is the outermost layer, so responses refused by Auth also carry its headers
+//! let server = Server::new(Handler {}.layer(Auth {}).layer(Cors {}));
+//! ```
+
+use embedded_io_async::{Read, Write};
+
+use crate::request::{Method, Request};
+use crate::response::{Responder, StatusCode};
+use crate::server::{HandlerError, RequestHandler, handle_allowed};
+use crate::websocket::Websocket;
+
+/// Middleware wrapping a `RequestHandler`.  A layer is given each request before the handler it
+/// wraps (`next`), and may inspect the request, respond itself without calling `next`, or add
+/// headers to the response with `Responder::with_default_headers` before passing the request on.
+/// The default implementations pass everything on to `next` unchanged.
+pub trait Layer {
+    /// Called in place of `next.handle_request`.  Requests answered from the methods declared by
+    /// `RequestHandler::allowed_methods`, the 204 No Content to an OPTIONS request and the 405
+    /// Method Not Allowed, are answered by `next` so they pass through the layer too.
+    fn handle_request<'client, 'buff, C: Read + Write + 'client, H: RequestHandler>(
+        &self,
+        req: Request<'buff>,
+        resp: Responder<'buff, 'client, C>,
+        next: &H,
+    ) -> impl Future<Output = Result<Option<Websocket<'client, C>>, HandlerError>> {
+        next.handle_request(req, resp)
+    }
+
+    /// Called in place of `next.handle_websocket` for a websocket returned by `handle_request`.
+    fn handle_websocket<'client, C: Read + Write + 'client, H: RequestHandler>(
+        &self,
+        websocket: Websocket<'client, C>,
+        buffer: &mut [u8],
+        next: &H,
+    ) -> impl Future<Output = Result<(), HandlerError>> {
+        next.handle_websocket(websocket, buffer)
+    }
+
+    /// Called in place of `next.handle_error` for an error response the server generates, such as
+    /// a 400 Bad Request for a request that cannot be parsed, or a 500 Internal Server Error when
+    /// the handler fails.
+    fn handle_error<'client, 'buff, C: Read + Write + 'client, H: RequestHandler>(
+        &self,
+        status: StatusCode,
+        resp: Responder<'buff, 'client, C>,
+        next: &H,
+    ) -> impl Future<Output = Result<(), HandlerError>> {
+        next.handle_error(status, resp)
+    }
+}
+
+/// A `RequestHandler` wrapped in a `Layer`, created with `RequestHandler::layer`.  The methods the
+/// wrapped handler allows are not declared by `Layered::allowed_methods`, but answered after the
+/// request has passed through the layer, so the server gives every request to the layer.
+pub struct Layered<H, L> {
+    handler: H,
+    layer: L,
+}
+
+impl<H: RequestHandler, L: Layer> Layered<H, L> {
+    /// Wrap `handler` in `layer`.
+    pub fn new(handler: H, layer: L) -> Self {
+        Self { handler, layer }
+    }
+}
+
+impl<H: RequestHandler, L: Layer> RequestHandler for Layered<H, L> {
+    async fn handle_request<'client, 'buff, C: Read + Write + 'client>(
+        &self,
+        req: Request<'buff>,
+        resp: Responder<'buff, 'client, C>,
+    ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+        self.layer
+            .handle_request(req, resp, &Allowed(&self.handler))
+            .await
+    }
+
+    async fn handle_websocket<'client, C: Read + Write + 'client>(
+        &self,
+        websocket: Websocket<'client, C>,
+        buffer: &mut [u8],
+    ) -> Result<(), HandlerError> {
+        self.layer
+            .handle_websocket(websocket, buffer, &self.handler)
+            .await
+    }
+
+    async fn handle_error<'client, 'buff, C: Read + Write + 'client>(
+        &self,
+        status: StatusCode,
+        resp: Responder<'buff, 'client, C>,
+    ) -> Result<(), HandlerError> {
+        self.layer.handle_error(status, resp, &self.handler).await
+    }
+}
+
+/// The handler a layer passes requests on to, answering those whose method is not allowed by the
+/// wrapped handler's `allowed_methods` as the server would.
+struct Allowed<'h, H>(&'h H);
+
+impl<H: RequestHandler> RequestHandler for Allowed<'_, H> {
+    async fn handle_request<'client, 'buff, C: Read + Write + 'client>(
+        &self,
+        req: Request<'buff>,
+        resp: Responder<'buff, 'client, C>,
+    ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+        handle_allowed(self.0, req, resp).await
+    }
+
+    async fn handle_websocket<'client, C: Read + Write + 'client>(
+        &self,
+        websocket: Websocket<'client, C>,
+        buffer: &mut [u8],
+    ) -> Result<(), HandlerError> {
+        self.0.handle_websocket(websocket, buffer).await
+    }
+
+    fn allowed_methods(&self, path: &str) -> Option<&[Method]> {
+        self.0.allowed_methods(path)
+    }
+
+    async fn handle_error<'client, 'buff, C: Read + Write + 'client>(
+        &self,
+        status: StatusCode,
+        resp: Responder<'buff, 'client, C>,
+    ) -> Result<(), HandlerError> {
+        self.0.handle_error(status, resp).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use core::cell::Cell;
+    use embedded_io_async::{ErrorKind, ErrorType};
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::header::{RequestHeader, ResponseHeader};
+    use crate::server::ServerConfig;
+
+    struct TestClient<'a> {
+        inner: &'a mut Vec<u8>,
+    }
+
+    impl<'a> ErrorType for TestClient<'a> {
+        type Error = ErrorKind;
+    }
+
+    impl<'a> Write for TestClient<'a> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.inner.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    impl<'a> Read for TestClient<'a> {
+        async fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Self::Error> {
+            Ok(0)
+        }
+    }
+
+    struct Handler {}
+
+    impl RequestHandler for Handler {
+        async fn handle_request<'client, 'buff, C: Read + Write + 'client>(
+            &self,
+            _req: Request<'buff>,
+            resp: Responder<'buff, 'client, C>,
+        ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+            resp.with_status(StatusCode::OK)
+                .await?
+                .with_body(b"handled")
+                .await?;
+            Ok(None)
+        }
+
+        async fn handle_websocket<'client, C: Read + Write + 'client>(
+            &self,
+            _websocket: Websocket<'client, C>,
+            _buffer: &mut [u8],
+        ) -> Result<(), HandlerError> {
+            Err(HandlerError::CustomError("handler"))
+        }
+
+        fn allowed_methods(&self, _path: &str) -> Option<&[Method]> {
+            Some(&[Method::GET])
+        }
+    }
+
+    /// Refuses requests without an Authorization header
+    struct Auth {}
+
+    impl Layer for Auth {
+        async fn handle_request<'client, 'buff, C: Read + Write + 'client, H: RequestHandler>(
+            &self,
+            req: Request<'buff>,
+            resp: Responder<'buff, 'client, C>,
+            next: &H,
+        ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+            if req
+                .get_header(RequestHeader::Other("Authorization", ""))
+                .is_none()
+            {
+                resp.with_status(StatusCode::NotFound)
+                    .await?
+                    .no_body()
+                    .await?;
+                return Ok(None);
+            }
+            next.handle_request(req, resp).await
+        }
+    }
+
+    /// Adds a header to responses, and counts the websockets passed through it
+    struct Tag {
+        header: &'static [ResponseHeader<'static>],
+        websockets: Cell<usize>,
+    }
+
+    impl Layer for Tag {
+        async fn handle_request<'client, 'buff, C: Read + Write + 'client, H: RequestHandler>(
+            &self,
+            req: Request<'buff>,
+            resp: Responder<'buff, 'client, C>,
+            next: &H,
+        ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+            next.handle_request(req, resp.with_default_headers(self.header)?)
+                .await
+        }
+
+        async fn handle_websocket<'client, C: Read + Write + 'client, H: RequestHandler>(
+            &self,
+            websocket: Websocket<'client, C>,
+            buffer: &mut [u8],
+            next: &H,
+        ) -> Result<(), HandlerError> {
+            self.websockets.set(self.websockets.get() + 1);
+            next.handle_websocket(websocket, buffer).await
+        }
+
+        async fn handle_error<'client, 'buff, C: Read + Write + 'client, H: RequestHandler>(
+            &self,
+            status: StatusCode,
+            resp: Responder<'buff, 'client, C>,
+            next: &H,
+        ) -> Result<(), HandlerError> {
+            next.handle_error(status, resp.with_default_headers(self.header)?)
+                .await
+        }
+    }
+
+    async fn respond<H: RequestHandler>(handler: &H, request: &str) -> std::string::String {
+        let mut buf = request.as_bytes().to_vec();
+        let req = Request::parse(&mut buf, usize::MAX, &ServerConfig::default()).unwrap();
+        let mut dst = Vec::new();
+        let mut client = TestClient { inner: &mut dst };
+        let resp = Responder::new(&req, &mut client);
+        handler.handle_request(req, resp).await.unwrap();
+        std::string::String::from_utf8(dst).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_layer_composition() {
+        let handler = Handler {}
+            .layer(Auth {})
+            .layer(Tag {
+                header: &[ResponseHeader::Vary("Origin")],
+                websockets: Cell::new(0),
+            })
+            .layer(Tag {
+                header: &[ResponseHeader::Other("X-Outer", "1")],
+                websockets: Cell::new(0),
+            });
+
+        // the outermost layer adds its headers first, and responses refused by Auth have them too
+        assert_eq!(
+            respond(&handler, "GET / HTTP/1.1\r\n\r\n").await,
+            "HTTP/1.1 404 Not Found\r\nX-Outer: 1\r\nVary: Origin\r\nContent-Length: 0\r\n\r\n"
+        );
+        assert_eq!(
+            respond(&handler, "GET / HTTP/1.1\r\nAuthorization: x\r\n\r\n").await,
+            "HTTP/1.1 200 OK\r\nX-Outer: 1\r\nVary: Origin\r\nContent-Length: 7\r\n\r\nhandled"
+        );
+
+        // the allowed methods are answered within the layers
+        assert_eq!(handler.allowed_methods("/"), None);
+
+        let mut dst = Vec::new();
+        let mut client = TestClient { inner: &mut dst };
+        assert_eq!(
+            handler
+                .handle_websocket(Websocket::new(&mut client), &mut [])
+                .await,
+            Err(HandlerError::CustomError("handler"))
+        );
+        assert_eq!(handler.layer.websockets.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_layer_allowed_methods_and_errors() {
+        let handler = Handler {}.layer(Auth {}).layer(Tag {
+            header: &[ResponseHeader::AccessControlAllowOrigin("*")],
+            websockets: Cell::new(0),
+        });
+
+        // preflight requests and 405s pass through every layer
+        assert_eq!(
+            respond(&handler, "OPTIONS / HTTP/1.1\r\nAuthorization: x\r\n\r\n").await,
+            "HTTP/1.1 204 No Content\r\nAllow: GET, HEAD, OPTIONS\r\n\
+            Access-Control-Allow-Origin: *\r\n\r\n"
+        );
+        assert_eq!(
+            respond(&handler, "OPTIONS / HTTP/1.1\r\n\r\n").await,
+            "HTTP/1.1 404 Not Found\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: 0\r\n\r\n"
+        );
+        assert_eq!(
+            respond(&handler, "POST / HTTP/1.1\r\nAuthorization: x\r\n\r\n").await,
+            "HTTP/1.1 405 Method Not Allowed\r\nAllow: GET, HEAD, OPTIONS\r\n\
+            Access-Control-Allow-Origin: *\r\nContent-Length: 0\r\n\r\n"
+        );
+
+        // so do the error responses the server generates
+        let mut dst = Vec::new();
+        let mut client = TestClient { inner: &mut dst };
+        handler
+            .handle_error(
+                StatusCode::BadRequest,
+                Responder::without_request(&mut client),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            std::string::String::from_utf8(dst).unwrap(),
+            "HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\
+            Access-Control-Allow-Origin: *\r\nContent-Length: 0\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_layer_default_header_limit() {
+        let mut buf = b"GET / HTTP/1.1\r\n\r\n".to_vec();
+        let req = Request::parse(&mut buf, usize::MAX, &ServerConfig::default()).unwrap();
+        let mut dst = Vec::new();
+        let mut client = TestClient { inner: &mut dst };
+        let mut resp = Responder::new(&req, &mut client);
+        for _ in 0..4 {
+            resp = resp.with_default_headers(&[]).unwrap();
+        }
+        // empty sets leave their slot free
+        resp = resp
+            .with_default_headers(&[ResponseHeader::Vary("Origin")])
+            .unwrap();
+        for _ in 0..3 {
+            resp = resp
+                .with_default_headers(&[ResponseHeader::Vary("Origin")])
+                .unwrap();
+        }
+        assert!(
+            resp.with_default_headers(&[ResponseHeader::Vary("Origin")])
+                .is_err()
+        );
+    }
+}
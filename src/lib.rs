@@ -7,11 +7,17 @@
 //!
 //! * encoding and decoding of HTTP requests and responses on the "wire" respectively.
 //! * encoding and decoding of websocket frames on the "wire".
+//! * a simple table based router (`router::Router`) matching methods and paths with parameters.
+//! * zero allocation parsing and percent-decoding of query string parameters (`query::Query`).
+//! * parsing of HTML form bodies (`Request::form`), and deserializing them with the `serde` feature.
+//! * streaming of `multipart/form-data` uploads (`Request::multipart`) without buffering whole files.
+//! * JSON request and response bodies (`Request::json`, `ResponderSending::json`) and websocket
+//!   messages (`Websocket::send_json`) with the `json` feature.
+//! * reading request cookies (`Request::cookies`) and setting them (`cookie::SetCookie`).
 //!
 //! This crate does **not** provide:
 //!
-//! * any mechanism for routing requests to specific handlers.
-//! * any higher level functionality for extracting data from paths, or request bodies.
+//! * any higher level functionality for extracting data from other types of request body.
 //!
 //! ## Basic Use
 //!
@@ -44,7 +50,7 @@
 //!         req: Request<'buff>,
 //!         resp: Responder<'buff, 'client, C>,
 //!     ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
-//!         match req.path {
+//!         match req.target.path() {
 //!            "/" => {
 //!                resp.with_status(StatusCode::OK)
 //!                    .await?
@@ -123,17 +129,35 @@
 #![warn(missing_docs)]
 
 mod ascii;
+mod chunked;
+pub mod cookie;
+pub mod form;
 /// HTTP Headers
 pub mod header;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod layer;
+pub mod multipart;
+pub mod pool;
+pub mod query;
 /// HTTP Requests
 pub mod request;
 /// HTTP responses
 pub mod response;
+pub mod router;
 /// HTTP server
 pub mod server;
+pub mod timer;
+pub mod uri;
+pub mod vhost;
 /// Websockets
 pub mod websocket;
 
+#[doc(hidden)]
+pub mod __private {
+    pub use embedded_io_async::{Read, Write};
+}
+
 use embedded_io_async::Write;
 
 pub(crate) enum WriteError {
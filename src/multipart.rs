@@ -0,0 +1,502 @@
+//! Streaming parser for `multipart/form-data` bodies, as sent by browsers uploading files from an
+//! `<input type=file>`.  Each part's headers are held in a buffer provided by the caller while the
+//! part's body is streamed through `embedded_io_async::Read`, so files larger than the buffer (such
+//! as firmware images) can be received.
+//!
+//! ```
+//! use embedded_io_async::{Read, Write};
+//!
+//! use weblite::request::Request;
+//! use weblite::response::{Responder, StatusCode};
+//! use weblite::server::HandlerError;
+//!
+//! async fn upload<C: Read + Write>(
+//!     req: Request<'_>,
+//!     mut resp: Responder<'_, '_, C>,
+//! ) -> Result<(), HandlerError> {
+//!     let mut buf = [0u8; 512];
+//!     let mut form = match req.multipart(resp.body_reader(), &mut buf) {
+//!         Ok(form) => form,
+//!         Err(_) => {
+//!             resp.with_status(StatusCode::BadRequest).await?.no_body().await?;
+//!             return Ok(());
+//!         }
+//!     };
+//!
+//!     while let Some(mut part) = form.next_part().await.map_err(|_| "bad upload")? {
+//!         if part.name() != Some("firmware") {
+//!             continue;
+//!         }
+//!         let mut chunk = [0u8; 256];
+//!         loop {
+//!             let n = part.read(&mut chunk).await.map_err(|_| "bad upload")?;
+//!             if n == 0 {
+//!                 break;
+//!             }
+//!             // write chunk[..n] to flash
+//!         }
+//!     }
+//!
+//!     drop(form);
+//!     resp.with_status(StatusCode::OK).await?.no_body().await?;
+//!     Ok(())
+//! }
+//! ```
+
+use embedded_io_async::{ErrorKind, ErrorType, Read};
+
+/// The media type of bodies holding multipart form data
+pub const MULTIPART_FORM_DATA: &str = "multipart/form-data";
+
+const DASHES: &[u8] = b"--";
+const CRLF: &[u8] = b"\r\n";
+const TRUNCATED: &str = "multipart body ended before the closing boundary";
+
+/// Errors reading a multipart body
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MultipartError {
+    /// The Content-Type is not `multipart/form-data`, or does not give a valid boundary
+    UnsupportedContentType,
+    /// The body is not valid multipart data
+    Malformed(&'static str),
+    /// The headers of a part do not fit in the buffer provided
+    BufferTooSmall,
+    /// Reading the body from the client failed
+    ReadError,
+}
+
+impl embedded_io_async::Error for MultipartError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::ReadError => ErrorKind::Other,
+            Self::BufferTooSmall => ErrorKind::OutOfMemory,
+            _ => ErrorKind::InvalidData,
+        }
+    }
+}
+
+/// The value of the parameter `name` in a header value such as
+/// `form-data; name="upload"; filename="image.bin"`, with any quotes removed.
+fn param<'h>(value: &'h str, name: &str) -> Option<&'h str> {
+    // the parameters follow the media type or disposition
+    let mut rest = &value[value.find(';')? + 1..];
+    loop {
+        let (key, after) = rest.split_once('=')?;
+        let after = after.trim_start();
+        let (found, after) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let close = quoted.find('"')?;
+                (&quoted[..close], &quoted[close + 1..])
+            }
+            None => {
+                let end = after.find(';').unwrap_or(after.len());
+                (after[..end].trim_end(), &after[end..])
+            }
+        };
+        if key.trim().eq_ignore_ascii_case(name) {
+            return Some(found);
+        }
+        rest = &after[after.find(';')? + 1..];
+    }
+}
+
+/// The boundary of a `multipart/form-data` Content-Type header value.
+pub fn boundary(content_type: &str) -> Option<&str> {
+    let media_type = content_type.split(';').next()?.trim();
+    if !media_type.eq_ignore_ascii_case(MULTIPART_FORM_DATA) {
+        return None;
+    }
+    param(content_type, "boundary").filter(|b| (1..=70).contains(&b.len()))
+}
+
+/// A parser reading the parts of a multipart body from `reader`, created with
+/// `Request::multipart` or `Multipart::new`.  Parts are read in order with `next_part`; any of a
+/// part's body not read when the next part is requested is skipped.
+pub struct Multipart<'m, R: Read> {
+    reader: R,
+    boundary: &'m [u8],
+    buf: &'m mut [u8],
+    // the current part's headers are held in buf[..base], and data received but not yet consumed
+    // in buf[start..end]
+    base: usize,
+    start: usize,
+    end: usize,
+    // whether the current part's body has been consumed up to the following delimiter
+    body_done: bool,
+    finished: bool,
+    name: Option<(usize, usize)>,
+    filename: Option<(usize, usize)>,
+    content_type: Option<(usize, usize)>,
+}
+
+impl<'m, R: Read> Multipart<'m, R> {
+    /// Read the parts separated by `boundary` from `reader`.  `buf` must be large enough to hold
+    /// the headers of any part, along with the delimiter between parts.
+    pub fn new(boundary: &'m str, reader: R, buf: &'m mut [u8]) -> Self {
+        // the first delimiter need not follow a line break, so one is supplied, and anything
+        // before it (the preamble) is treated as the body of a part to be skipped
+        let start = buf.len().min(CRLF.len());
+        buf[..start].copy_from_slice(&CRLF[..start]);
+        Self {
+            reader,
+            boundary: boundary.as_bytes(),
+            buf,
+            base: 0,
+            start: 0,
+            end: start,
+            body_done: false,
+            finished: false,
+            name: None,
+            filename: None,
+            content_type: None,
+        }
+    }
+
+    /// Move the data not yet consumed to follow the part's headers, and read more from the reader.
+    async fn fill(&mut self) -> Result<(), MultipartError> {
+        self.buf.copy_within(self.start..self.end, self.base);
+        self.end -= self.start - self.base;
+        self.start = self.base;
+        if self.end == self.buf.len() {
+            return Err(MultipartError::BufferTooSmall);
+        }
+
+        match self.reader.read(&mut self.buf[self.end..]).await {
+            Ok(0) => Err(MultipartError::Malformed(TRUNCATED)),
+            Ok(n) => {
+                self.end += n;
+                Ok(())
+            }
+            Err(_) => Err(MultipartError::ReadError),
+        }
+    }
+
+    /// Read until at least `n` bytes are available.
+    async fn ensure(&mut self, n: usize) -> Result<(), MultipartError> {
+        while self.end - self.start < n {
+            self.fill().await?;
+        }
+        Ok(())
+    }
+
+    /// Whether `data` begins with a delimiter (`\r\n--boundary`).  `None` if it does not, or
+    /// `Some(false)` if `data` ends part way through what may be a delimiter.
+    fn delimiter(&self, data: &[u8]) -> Option<bool> {
+        let expected = CRLF.iter().chain(DASHES).chain(self.boundary);
+        for (i, b) in expected.enumerate() {
+            match data.get(i) {
+                None => return Some(false),
+                Some(d) if d != b => return None,
+                Some(_) => {}
+            }
+        }
+        Some(true)
+    }
+
+    /// Consume up to `max` bytes of the current part's body, returning the range of `buf` they are
+    /// held in.  The range is empty once the body has been consumed.
+    async fn body(&mut self, max: usize) -> Result<(usize, usize), MultipartError> {
+        while !self.body_done {
+            let window = &self.buf[self.start..self.end];
+            let (pos, found) = (0..window.len())
+                .find_map(|i| self.delimiter(&window[i..]).map(|found| (i, found)))
+                .unwrap_or((window.len(), false));
+
+            if pos > 0 {
+                let n = pos.min(max);
+                self.start += n;
+                return Ok((self.start - n, self.start));
+            }
+            match found {
+                true => self.body_done = true,
+                // a delimiter may begin here, more data is needed to tell
+                false => self.fill().await?,
+            }
+        }
+        Ok((self.start, self.start))
+    }
+
+    /// Returns the next part, or `None` after the last part.  The rest of the previous part's body
+    /// is skipped.
+    pub async fn next_part(&mut self) -> Result<Option<Part<'_, 'm, R>>, MultipartError> {
+        if self.finished {
+            return Ok(None);
+        }
+        while !self.body_done {
+            self.body(usize::MAX).await?;
+        }
+
+        // the previous part's headers are no longer needed
+        self.base = 0;
+        self.name = None;
+        self.filename = None;
+        self.content_type = None;
+
+        // the delimiter is followed by `--` after the last part, otherwise a line break
+        let delimiter = CRLF.len() + DASHES.len() + self.boundary.len();
+        self.ensure(delimiter + 2).await?;
+        self.start += delimiter;
+        match &self.buf[self.start..self.start + 2] {
+            DASHES => {
+                self.finished = true;
+                return Ok(None);
+            }
+            CRLF => self.start += 2,
+            _ => return Err(MultipartError::Malformed("malformed multipart delimiter")),
+        }
+
+        // the headers end at a blank line, which is all there is of a part without headers
+        let headers_len = loop {
+            let window = &self.buf[self.start..self.end];
+            if window.starts_with(CRLF) {
+                break 0;
+            }
+            if let Some(p) = window.windows(4).position(|w| w == b"\r\n\r\n") {
+                break p + CRLF.len();
+            }
+            self.ensure(self.end - self.start + 1).await?;
+        };
+
+        // keep the headers at the start of the buffer while the body is streamed
+        self.buf.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = headers_len + CRLF.len();
+        self.base = self.start;
+        self.body_done = false;
+        self.parse_headers(headers_len)?;
+
+        Ok(Some(Part { multipart: self }))
+    }
+
+    /// Record where the headers of interest are within the first `len` bytes of the buffer.
+    fn parse_headers(&mut self, len: usize) -> Result<(), MultipartError> {
+        let headers = str::from_utf8(&self.buf[..len])
+            .map_err(|_| MultipartError::Malformed("multipart headers are not valid utf8"))?;
+        let span = |s: &str| {
+            let start = s.as_ptr() as usize - headers.as_ptr() as usize;
+            (start, start + s.len())
+        };
+
+        let (mut name, mut filename, mut content_type) = (None, None, None);
+        for line in headers.split("\r\n").filter(|l| !l.is_empty()) {
+            let Some((header, value)) = line.split_once(':') else {
+                return Err(MultipartError::Malformed("malformed multipart header"));
+            };
+            let value = value.trim();
+            if header.trim().eq_ignore_ascii_case("Content-Disposition") {
+                name = param(value, "name").map(span);
+                filename = param(value, "filename").map(span);
+            } else if header.trim().eq_ignore_ascii_case("Content-Type") {
+                content_type = Some(span(value));
+            }
+        }
+
+        self.name = name;
+        self.filename = filename;
+        self.content_type = content_type;
+        Ok(())
+    }
+
+    fn header(&self, span: Option<(usize, usize)>) -> Option<&str> {
+        // the headers were validated as utf8 when parsed
+        span.map(|(start, end)| str::from_utf8(&self.buf[start..end]).unwrap())
+    }
+}
+
+/// A part of a multipart body, returned by `Multipart::next_part`.  The part's body is read with
+/// `embedded_io_async::Read`, returning `Ok(0)` at the end of the part.
+pub struct Part<'p, 'm, R: Read> {
+    multipart: &'p mut Multipart<'m, R>,
+}
+
+impl<R: Read> Part<'_, '_, R> {
+    /// The name of the form field, from the part's Content-Disposition header
+    pub fn name(&self) -> Option<&str> {
+        self.multipart.header(self.multipart.name)
+    }
+
+    /// The name of the uploaded file, from the part's Content-Disposition header
+    pub fn filename(&self) -> Option<&str> {
+        self.multipart.header(self.multipart.filename)
+    }
+
+    /// The part's Content-Type, if given
+    pub fn content_type(&self) -> Option<&str> {
+        self.multipart.header(self.multipart.content_type)
+    }
+}
+
+impl<R: Read> ErrorType for Part<'_, '_, R> {
+    type Error = MultipartError;
+}
+
+impl<R: Read> Read for Part<'_, '_, R> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let (start, end) = self.multipart.body(buf.len()).await?;
+        buf[..end - start].copy_from_slice(&self.multipart.buf[start..end]);
+        Ok(end - start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::string::String;
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Returns at most `limit` bytes from each read
+    struct SlowReader<'a> {
+        data: &'a [u8],
+        limit: usize,
+    }
+
+    impl ErrorType for SlowReader<'_> {
+        type Error = ErrorKind;
+    }
+
+    impl Read for SlowReader<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let n = buf.len().min(self.limit).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    const BODY: &str = "preamble\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"label\"\r\n\
+        \r\n\
+        kitchen\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"firmware\"; filename=\"fw; v2.bin\"\r\n\
+        Content-Type: application/octet-stream\r\n\
+        \r\n\
+        \r\n--XyY-not-a-delimiter\r\n-\r\n\
+        --XyZ\r\n\
+        \r\n\
+        no headers\r\n\
+        --XyZ--\r\n\
+        epilogue";
+
+    /// Reads every part, returning the name, filename, content type and body of each
+    async fn parts(
+        body: &str,
+        limit: usize,
+        buf: &mut [u8],
+    ) -> Result<Vec<(Option<String>, Option<String>, Option<String>, String)>, MultipartError> {
+        let reader = SlowReader {
+            data: body.as_bytes(),
+            limit,
+        };
+        let mut multipart = Multipart::new("XyZ", reader, buf);
+        let mut parts = Vec::new();
+        while let Some(mut part) = multipart.next_part().await? {
+            let mut body = Vec::new();
+            let mut chunk = [0u8; 3];
+            loop {
+                let n = part.read(&mut chunk).await?;
+                if n == 0 {
+                    break;
+                }
+                body.extend_from_slice(&chunk[..n]);
+            }
+            parts.push((
+                part.name().map(String::from),
+                part.filename().map(String::from),
+                part.content_type().map(String::from),
+                String::from_utf8(body).unwrap(),
+            ));
+        }
+        Ok(parts)
+    }
+
+    #[tokio::test]
+    async fn test_multipart_parts() {
+        for limit in [1, 2, 7, 64, 1024] {
+            let mut buf = [0u8; 160];
+            assert_eq!(
+                parts(BODY, limit, &mut buf).await,
+                Ok(std::vec![
+                    (Some("label".into()), None, None, "kitchen".into()),
+                    (
+                        Some("firmware".into()),
+                        Some("fw; v2.bin".into()),
+                        Some("application/octet-stream".into()),
+                        "\r\n--XyY-not-a-delimiter\r\n-".into()
+                    ),
+                    (None, None, None, "no headers".into()),
+                ]),
+                "{limit}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_multipart_skipped_parts() {
+        let reader = SlowReader {
+            data: BODY.as_bytes(),
+            limit: 5,
+        };
+        let mut buf = [0u8; 160];
+        let mut multipart = Multipart::new("XyZ", reader, &mut buf);
+        let mut names = Vec::new();
+        while let Some(part) = multipart.next_part().await.unwrap() {
+            names.push(part.name().map(String::from));
+        }
+        assert_eq!(names, [Some("label".into()), Some("firmware".into()), None]);
+        assert!(multipart.next_part().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_errors() {
+        let mut buf = [0u8; 160];
+        assert_eq!(
+            parts("--XyZ\r\n\r\nunfinished", 64, &mut buf).await,
+            Err(MultipartError::Malformed(TRUNCATED))
+        );
+        assert_eq!(
+            parts("--XyZ\r\nContent-Type", 64, &mut buf).await,
+            Err(MultipartError::Malformed(TRUNCATED))
+        );
+        assert_eq!(
+            parts("--XyZ!!\r\n\r\n--XyZ--", 64, &mut buf).await,
+            Err(MultipartError::Malformed("malformed multipart delimiter"))
+        );
+        assert_eq!(
+            parts("--XyZ\r\nbroken\r\n\r\n\r\n--XyZ--", 64, &mut buf).await,
+            Err(MultipartError::Malformed("malformed multipart header"))
+        );
+
+        // the headers must fit in the buffer
+        let mut buf = [0u8; 40];
+        assert_eq!(
+            parts(BODY, 64, &mut buf).await,
+            Err(MultipartError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_multipart_boundary() {
+        assert_eq!(
+            boundary("multipart/form-data; boundary=----WebKitFormBoundary7MA4"),
+            Some("----WebKitFormBoundary7MA4")
+        );
+        assert_eq!(
+            boundary("Multipart/Form-Data; charset=utf-8; boundary=\"a b\""),
+            Some("a b")
+        );
+        assert_eq!(boundary("multipart/form-data"), None);
+        assert_eq!(boundary("multipart/form-data; boundary="), None);
+        assert_eq!(
+            boundary("application/x-www-form-urlencoded; boundary=x"),
+            None
+        );
+    }
+}
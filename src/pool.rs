@@ -0,0 +1,409 @@
+//! A `ServerPool` serves a fixed number of client connections concurrently on a single executor,
+//! without allocation.  The pool owns a receive buffer for each of its connection slots, and each
+//! slot is given an `Accept` implementation (e.g. wrapping an `embassy_net::tcp::TcpSocket`) from
+//! which it obtains client connections to serve.  All slots share the one `Server`, and so the one
+//! `RequestHandler`.
+//!
+//! How many slots are busy is published in a `PoolStatus`.  As the status is held outside the
+//! pool, the handler can also be given a reference to it in order to make decisions based on the
+//! spare capacity, such as refusing a websocket upgrade that would occupy the last idle slot.
+//!
+//! ```
+//! # use embedded_io_async::{Read, Write};
+//! # use weblite::request::Request;
+//! # use weblite::response::Responder;
+//! # use weblite::websocket::Websocket;
+//! # use weblite::server::{RequestHandler, HandlerError, Server};
+//! use weblite::pool::{Accept, PoolStatus, ServerPool};
+//!
+//! # struct Socket;
+//! # impl embedded_io_async::ErrorType for Socket {
+//! #     type Error = embedded_io_async::ErrorKind;
+//! # }
+//! # impl Read for Socket {
+//! #     async fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Self::Error> { Ok(0) }
+//! # }
+//! # impl Write for Socket {
+//! #     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> { Ok(buf.len()) }
+//! # }
+//! # impl Socket {
+//! #     async fn accept(&mut self, _port: u16) -> Result<(), ()> {
+//! #         core::future::pending().await
+//! #     }
+//! #     fn close(&mut self) {}
+//! # }
+//! #
+//! static STATUS: PoolStatus = PoolStatus::new();
+//!
+//! struct MyHandler {
+//!     status: &'static PoolStatus,
+//! }
+//!
+//! # impl RequestHandler for MyHandler {
+//! #     async fn handle_request<'client, 'buff, C: Read + Write + 'client>(
+//! #         &self,
+//! #         req: Request<'buff>,
+//! #         resp: Responder<'buff, 'client, C>,
+//! #     ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+//! #         Ok(None)
+//! #     }
+//! # }
+//! #
+//! /// Wraps a TCP socket (not shown) that is reused for each connection accepted on port 80
+//! struct Listener {
+//!     socket: Socket,
+//! }
+//!
+//! impl Accept for Listener {
+//!     type Connection<'a> = &'a mut Socket;
+//!     type Error = ();
+//!
+//!     async fn accept(&mut self) -> Result<Self::Connection<'_>, Self::Error> {
+//!         self.socket.accept(80).await?;
+//!         Ok(&mut self.socket)
+//!     }
+//!
+//!     async fn close(&mut self) {
+//!         self.socket.close();
+//!     }
+//! }
+//!
+//! async fn run() {
+//!     let server = Server::new(MyHandler { status: &STATUS });
+//!     let mut pool = ServerPool::<_, _, 2, 2048>::new(server, &STATUS);
+//!
+//!     pool.run([
+//!         Listener { socket: Socket },
+//!         Listener { socket: Socket },
+//!     ])
+//!     .await;
+//! }
+//! ```
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use embassy_futures::join::join_array;
+use embassy_futures::select::{Either, select};
+use embassy_futures::yield_now;
+use embedded_io_async::{Read, Write};
+
+use crate::server::{RequestHandler, Server, Shutdown};
+use crate::timer::Timer;
+
+/// Trait implemented by the source of client connections for a slot in a `ServerPool`.
+pub trait Accept {
+    /// The client connection returned by `accept`
+    type Connection<'a>: Read + Write
+    where
+        Self: 'a;
+
+    /// Error returned by `accept`
+    type Error;
+
+    /// Wait for a new client connection.  If an error is returned the pool calls `accept` again.
+    fn accept(&mut self) -> impl Future<Output = Result<Self::Connection<'_>, Self::Error>>;
+
+    /// Called after the pool has finished serving a connection returned by `accept`.  For
+    /// resources such as sockets that are reused between connections, this is where the
+    /// connection should be closed.
+    fn close(&mut self) -> impl Future<Output = ()> {
+        async {}
+    }
+}
+
+/// Occupancy of the connection slots of a `ServerPool`.  It is updated by the pool, and may be
+/// read from anywhere, including from within a `RequestHandler`.
+#[derive(Debug, Default)]
+pub struct PoolStatus {
+    // the pool's slots all run on one executor, so only atomic loads and stores are needed which
+    // keeps this usable on targets without atomic read-modify-write operations.
+    busy: AtomicUsize,
+    capacity: AtomicUsize,
+}
+
+impl PoolStatus {
+    /// Construct a new PoolStatus.  It is a const fn so that it may be used to initialise a static.
+    pub const fn new() -> Self {
+        Self {
+            busy: AtomicUsize::new(0),
+            capacity: AtomicUsize::new(0),
+        }
+    }
+
+    /// Number of slots currently serving a client connection
+    pub fn busy(&self) -> usize {
+        self.busy.load(Ordering::Relaxed)
+    }
+
+    /// Number of slots waiting for a client connection
+    pub fn idle(&self) -> usize {
+        self.capacity().saturating_sub(self.busy())
+    }
+
+    /// Total number of slots in the pool.  This is 0 until the pool is running.
+    pub fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    fn add_busy(&self, delta: isize) {
+        self.busy
+            .store(self.busy().saturating_add_signed(delta), Ordering::Relaxed);
+    }
+}
+
+/// ServerPool serves up to `N` client connections concurrently using `N` buffers each of `B`
+/// bytes.
+pub struct ServerPool<'s, H, T, const N: usize, const B: usize> {
+    server: Server<H, T>,
+    status: &'s PoolStatus,
+    buffers: [[u8; B]; N],
+}
+
+impl<'s, H, T, const N: usize, const B: usize> ServerPool<'s, H, T, N, B>
+where
+    H: RequestHandler,
+    T: Timer,
+{
+    /// Construct a ServerPool that serves connections with the provided `Server`, publishing the
+    /// occupancy of its slots to `status`.
+    pub fn new(server: Server<H, T>, status: &'s PoolStatus) -> Self {
+        Self {
+            server,
+            status,
+            buffers: [[0u8; B]; N],
+        }
+    }
+
+    /// Number of slots currently serving a client connection
+    pub fn busy(&self) -> usize {
+        self.status.busy()
+    }
+
+    /// Serve connections from each of the acceptors concurrently, one slot per acceptor.  Each
+    /// slot repeatedly accepts a connection and serves it until the connection is finished with.
+    /// Errors serving a connection are not returned, the connection is simply closed.
+    pub async fn run<A: Accept>(&mut self, acceptors: [A; N]) {
+        self.run_until(acceptors, &core::future::pending).await
+    }
+
+    /// As per `run()`, but once the shutdown signal completes, slots stop accepting connections
+    /// and connections being served are shut down as per `Server::serve_until()`.  Returns when
+    /// all slots have finished.
+    pub async fn run_until<A: Accept, S: Shutdown>(&mut self, mut acceptors: [A; N], shutdown: &S) {
+        self.status.capacity.store(N, Ordering::Relaxed);
+
+        let server = &self.server;
+        let status = self.status;
+        let mut buffers = self.buffers.each_mut().into_iter();
+
+        // N acceptors and N buffers, so there is always a buffer for each acceptor
+        let slots = acceptors
+            .each_mut()
+            .map(|acceptor| run_slot(server, status, acceptor, buffers.next().unwrap(), shutdown));
+
+        join_array(slots).await;
+    }
+}
+
+async fn run_slot<H, T, A, S>(
+    server: &Server<H, T>,
+    status: &PoolStatus,
+    acceptor: &mut A,
+    buffer: &mut [u8],
+    shutdown: &S,
+) where
+    H: RequestHandler,
+    T: Timer,
+    A: Accept,
+    S: Shutdown,
+{
+    loop {
+        let mut conn = match select(shutdown.requested(), acceptor.accept()).await {
+            Either::First(()) => return,
+            Either::Second(Ok(conn)) => conn,
+            Either::Second(Err(_)) => {
+                // let the other slots run in case the acceptor fails without ever waiting
+                yield_now().await;
+                continue;
+            }
+        };
+
+        status.add_busy(1);
+        let _ = server.serve_until(&mut conn, buffer, shutdown).await;
+        status.add_busy(-1);
+
+        drop(conn);
+        acceptor.close().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+
+    use embedded_io_async::{ErrorKind, ErrorType};
+
+    use super::*;
+    use crate::request::Request;
+    use crate::response::{Responder, StatusCode};
+    use crate::server::HandlerError;
+    use crate::websocket::Websocket;
+
+    struct TestConn {
+        input: &'static [u8],
+        output: Vec<u8>,
+    }
+
+    impl ErrorType for TestConn {
+        type Error = ErrorKind;
+    }
+
+    impl Read for TestConn {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let n = self.input.len().min(buf.len());
+            buf[..n].copy_from_slice(&self.input[..n]);
+            self.input = &self.input[n..];
+            Ok(n)
+        }
+    }
+
+    impl Write for TestConn {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    /// Accepts a single connection, then waits forever
+    struct TestAcceptor {
+        conn: TestConn,
+        accepted: bool,
+        closed: bool,
+    }
+
+    impl TestAcceptor {
+        fn new(input: &'static [u8]) -> Self {
+            Self {
+                conn: TestConn {
+                    input,
+                    output: Vec::new(),
+                },
+                accepted: false,
+                closed: false,
+            }
+        }
+    }
+
+    impl Accept for &mut TestAcceptor {
+        type Connection<'a>
+            = &'a mut TestConn
+        where
+            Self: 'a;
+        type Error = ();
+
+        async fn accept(&mut self) -> Result<Self::Connection<'_>, Self::Error> {
+            if self.accepted {
+                core::future::pending::<()>().await;
+            }
+            self.accepted = true;
+            Ok(&mut self.conn)
+        }
+
+        async fn close(&mut self) {
+            self.closed = true;
+        }
+    }
+
+    /// Records the most slots seen busy while handling requests
+    struct Handler<'a> {
+        status: &'a PoolStatus,
+        max_busy: &'a AtomicUsize,
+    }
+
+    impl RequestHandler for Handler<'_> {
+        async fn handle_request<'client, 'buff, C: Read + Write + 'client>(
+            &self,
+            _req: Request<'buff>,
+            resp: Responder<'buff, 'client, C>,
+        ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+            // give the other slots a chance to accept their connection
+            yield_now().await;
+
+            let busy = self.status.busy();
+            if busy > self.max_busy.load(Ordering::Relaxed) {
+                self.max_busy.store(busy, Ordering::Relaxed);
+            }
+
+            resp.with_status(StatusCode::OK)
+                .await?
+                .with_body(b"ok")
+                .await?;
+            Ok(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_pool() {
+        let status = PoolStatus::new();
+        let max_busy = AtomicUsize::new(0);
+        let handler = Handler {
+            status: &status,
+            max_busy: &max_busy,
+        };
+        let mut pool = ServerPool::<_, _, 2, 256>::new(Server::new(handler), &status);
+
+        let mut one = TestAcceptor::new(b"GET / HTTP/1.1\r\n\r\n");
+        let mut two = TestAcceptor::new(b"GET / HTTP/1.0\r\n\r\n");
+
+        let wait_closed = async {
+            while status.capacity() == 0 || status.busy() > 0 {
+                yield_now().await;
+            }
+        };
+
+        match select(pool.run([&mut one, &mut two]), wait_closed).await {
+            Either::First(()) => panic!("pool stopped running"),
+            Either::Second(()) => {}
+        }
+
+        assert_eq!(max_busy.load(Ordering::Relaxed), 2);
+        assert_eq!(status.capacity(), 2);
+        assert_eq!(status.idle(), 2);
+        assert!(one.closed && two.closed);
+        assert_eq!(
+            one.conn.output,
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"
+        );
+        assert_eq!(
+            two.conn.output,
+            b"HTTP/1.0 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_server_pool_shutdown() {
+        let status = PoolStatus::new();
+        let max_busy = AtomicUsize::new(0);
+        let handler = Handler {
+            status: &status,
+            max_busy: &max_busy,
+        };
+        let mut pool = ServerPool::<_, _, 2, 256>::new(Server::new(handler), &status);
+
+        let mut one = TestAcceptor::new(b"GET / HTTP/1.1\r\n\r\n");
+        let mut two = TestAcceptor::new(b"GET / HTTP/1.1\r\n\r\n");
+
+        // shutdown once both connections have been served
+        let shutdown = || async {
+            while max_busy.load(Ordering::Relaxed) < 2 || status.busy() > 0 {
+                yield_now().await;
+            }
+        };
+
+        pool.run_until([&mut one, &mut two], &shutdown).await;
+
+        assert_eq!(status.busy(), 0);
+        assert!(one.closed && two.closed);
+    }
+}
@@ -0,0 +1,245 @@
+//! Query strings (`?limit=10&name=my%20device`) and the percent-decoding they use.  Nothing is
+//! allocated: parameters are borrowed from the request as sent, and decoded on request into a
+//! buffer provided by the caller.
+//!
+//! ```
+//! use weblite::query::Query;
+//!
+//! let query = Query::new("limit=10&name=my%20device&verbose");
+//!
+//! assert_eq!(query.get("limit"), Some("10"));
+//! assert_eq!(query.get_parsed::<u32>("limit"), Some(Ok(10)));
+//! assert_eq!(query.get("verbose"), Some(""));
+//!
+//! let mut buf = [0u8; 32];
+//! assert_eq!(query.get_decoded("name", &mut buf), Some(Ok("my device")));
+//! ```
+
+use core::str::FromStr;
+
+/// Values decoded by `Query::get_parsed` must fit in a buffer of this many bytes
+pub const MAX_PARSED_LEN: usize = 64;
+
+/// Errors decoding a percent-encoded value
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeError {
+    /// A `%` was not followed by two hex digits, or the decoded value is not valid utf8
+    Malformed,
+    /// The decoded value does not fit in the buffer provided
+    BufferTooSmall,
+    /// The decoded value could not be parsed into the type requested
+    InvalidValue,
+}
+
+/// The value of a hex digit
+fn hex(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decode the byte starting at `encoded[i]`, returning it and the number of bytes it occupied.
+/// A `+` decodes to a space.
+fn decode_byte(encoded: &[u8], i: usize) -> Result<(u8, usize), DecodeError> {
+    match encoded[i] {
+        b'+' => Ok((b' ', 1)),
+        b'%' => match (
+            encoded.get(i + 1).copied().and_then(hex),
+            encoded.get(i + 2).copied().and_then(hex),
+        ) {
+            (Some(high), Some(low)) => Ok((high << 4 | low, 3)),
+            _ => Err(DecodeError::Malformed),
+        },
+        b => Ok((b, 1)),
+    }
+}
+
+/// Whether `encoded` decodes to `name`, without needing a buffer
+pub(crate) fn decodes_to(encoded: &str, name: &str) -> bool {
+    let encoded = encoded.as_bytes();
+    let mut name = name.bytes();
+    let mut i = 0;
+    while i < encoded.len() {
+        match decode_byte(encoded, i) {
+            Ok((b, n)) if name.next() == Some(b) => i += n,
+            _ => return false,
+        }
+    }
+    name.next().is_none()
+}
+
+/// Percent-decode `encoded` into `buf`, decoding `+` as a space, and return the decoded value.
+pub fn decode<'b>(encoded: &str, buf: &'b mut [u8]) -> Result<&'b str, DecodeError> {
+    let encoded = encoded.as_bytes();
+    let mut len = 0;
+    let mut i = 0;
+    while i < encoded.len() {
+        let (b, n) = decode_byte(encoded, i)?;
+        *buf.get_mut(len).ok_or(DecodeError::BufferTooSmall)? = b;
+        len += 1;
+        i += n;
+    }
+    str::from_utf8(&buf[..len]).map_err(|_| DecodeError::Malformed)
+}
+
+/// Percent-decode the value held in `buf` in place, decoding `+` as a space, and return the decoded
+/// value.  A value never grows when decoded, so this needs no other buffer.
+pub fn decode_in_place(buf: &mut [u8]) -> Result<&str, DecodeError> {
+    let mut len = 0;
+    let mut i = 0;
+    while i < buf.len() {
+        let (b, n) = decode_byte(buf, i)?;
+        buf[len] = b;
+        len += 1;
+        i += n;
+    }
+    str::from_utf8(&buf[..len]).map_err(|_| DecodeError::Malformed)
+}
+
+/// The parameters of a query string, e.g. `a=1&b=2`.  Iterating yields each `(key, value)` pair as
+/// sent, still percent-encoded.  A parameter without an `=` has an empty value.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Query<'a> {
+    raw: &'a str,
+}
+
+impl<'a> Query<'a> {
+    /// Parameters from a query string, without the leading `?`
+    pub fn new(raw: &'a str) -> Self {
+        Self { raw }
+    }
+
+    /// The query string as sent
+    pub fn as_str(&self) -> &'a str {
+        self.raw
+    }
+
+    /// An iterator over the `(key, value)` pairs, still percent-encoded
+    pub fn iter(&self) -> Pairs<'a> {
+        Pairs {
+            rest: self.raw.split('&'),
+        }
+    }
+
+    /// The first value for the parameter `name`, still percent-encoded.  Keys are compared after
+    /// decoding, so `get("a b")` finds `a+b=1`.
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.iter()
+            .find(|(key, _)| decodes_to(key, name))
+            .map(|(_, value)| value)
+    }
+
+    /// The first value for the parameter `name`, percent-decoded into `buf`.
+    pub fn get_decoded<'b>(
+        &self,
+        name: &str,
+        buf: &'b mut [u8],
+    ) -> Option<Result<&'b str, DecodeError>> {
+        self.get(name).map(|value| decode(value, buf))
+    }
+
+    /// The first value for the parameter `name`, decoded and parsed as a `T`, e.g.
+    /// `get_parsed::<u32>("limit")`.  The decoded value must be no longer than `MAX_PARSED_LEN`.
+    pub fn get_parsed<T: FromStr>(&self, name: &str) -> Option<Result<T, DecodeError>> {
+        let mut buf = [0u8; MAX_PARSED_LEN];
+        self.get_decoded(name, &mut buf)
+            .map(|value| value?.parse().map_err(|_| DecodeError::InvalidValue))
+    }
+}
+
+impl<'a> IntoIterator for Query<'a> {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Pairs<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the `(key, value)` pairs of a query, created by `Query::iter`
+pub struct Pairs<'a> {
+    rest: core::str::Split<'a, char>,
+}
+
+impl<'a> Iterator for Pairs<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // empty parameters (`a=1&&b=2`) are skipped
+        let param = self.rest.by_ref().find(|p| !p.is_empty())?;
+        Some(param.split_once('=').unwrap_or((param, "")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn test_query_pairs() {
+        let query = Query::new("a=1&&b=x%3Dy&flag&a=2&c+d=%20");
+        assert_eq!(
+            query.iter().collect::<Vec<_>>(),
+            [
+                ("a", "1"),
+                ("b", "x%3Dy"),
+                ("flag", ""),
+                ("a", "2"),
+                ("c+d", "%20")
+            ]
+        );
+
+        assert_eq!(query.get("a"), Some("1"));
+        assert_eq!(query.get("flag"), Some(""));
+        assert_eq!(query.get("c d"), Some("%20"));
+        assert_eq!(query.get("missing"), None);
+        assert_eq!(Query::new("").iter().count(), 0);
+        assert_eq!(Query::default().get("a"), None);
+
+        let mut buf = [0u8; 8];
+        assert_eq!(query.get_decoded("b", &mut buf), Some(Ok("x=y")));
+        assert_eq!(query.get_decoded("c d", &mut buf), Some(Ok(" ")));
+        assert_eq!(
+            Query::new("a=123456789").get_decoded("a", &mut buf),
+            Some(Err(DecodeError::BufferTooSmall))
+        );
+    }
+
+    #[test]
+    fn test_query_get_parsed() {
+        let query = Query::new("limit=10&ratio=%2D0.5&name=abc&big=99999999999");
+        assert_eq!(query.get_parsed::<u32>("limit"), Some(Ok(10)));
+        assert_eq!(query.get_parsed::<f32>("ratio"), Some(Ok(-0.5)));
+        assert_eq!(
+            query.get_parsed::<u32>("name"),
+            Some(Err(DecodeError::InvalidValue))
+        );
+        assert_eq!(
+            query.get_parsed::<u32>("big"),
+            Some(Err(DecodeError::InvalidValue))
+        );
+        assert_eq!(query.get_parsed::<u32>("missing"), None);
+    }
+
+    #[test]
+    fn test_percent_decoding() {
+        let mut buf = [0u8; 32];
+        assert_eq!(decode("a+b%20c%2fd", &mut buf), Ok("a b c/d"));
+        assert_eq!(decode("%E2%9C%93", &mut buf), Ok("\u{2713}"));
+        assert_eq!(decode("", &mut buf), Ok(""));
+        assert_eq!(decode("%", &mut buf), Err(DecodeError::Malformed));
+        assert_eq!(decode("%4", &mut buf), Err(DecodeError::Malformed));
+        assert_eq!(decode("%zz", &mut buf), Err(DecodeError::Malformed));
+        assert_eq!(decode("%FF", &mut buf), Err(DecodeError::Malformed));
+
+        let mut value = *b"my%20device+name";
+        assert_eq!(decode_in_place(&mut value), Ok("my device name"));
+    }
+}
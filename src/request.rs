@@ -1,7 +1,19 @@
 use core::mem::discriminant;
+use core::ops::Range;
+
+use embedded_io_async::Read;
 
 use crate::ascii::{COLON, CR, LF, SP};
-use crate::header::RequestHeader;
+use crate::chunked::ChunkedDecoder;
+use crate::cookie::Cookies;
+use crate::form::{FormError, is_form};
+use crate::header::{HeaderValues, Headers, RequestHeader, has_token};
+#[cfg(feature = "json")]
+use crate::json::{self, JsonError};
+use crate::multipart::{Multipart, MultipartError, boundary};
+use crate::query::{DecodeError, Query};
+use crate::server::ServerConfig;
+use crate::uri::{self, Target};
 
 const GET: &[u8] = "GET".as_bytes();
 const POST: &[u8] = "POST".as_bytes();
@@ -11,15 +23,35 @@ const DELETE: &[u8] = "DELETE".as_bytes();
 const OPTIONS: &[u8] = "OPTIONS".as_bytes();
 const HEAD: &[u8] = "HEAD".as_bytes();
 
+const AMBIGUOUS_LENGTH: &str = "request body length is ambiguous";
+
+const HTTP_1_0: &[u8] = "HTTP/1.0".as_bytes();
+const HTTP_1_1: &[u8] = "HTTP/1.1".as_bytes();
+
 #[derive(PartialEq, Debug)]
 pub(crate) enum RequestError {
     Incomplete(Option<usize>),
     ProtocolError(&'static str),
+    UnsupportedVersion,
+    UriTooLong,
+    HeadersTooLarge,
+    BodyTooLarge,
+    /// The headers are complete and the client is waiting for a 100 Continue before sending the
+    /// body of the indicated length.
+    ExpectsContinue(usize),
+    ExpectationFailed,
+}
+
+/// How the length of the request body is indicated
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum BodyFraming {
+    ContentLength,
+    Chunked,
 }
 
 /// Method such as GET. POST, DELETE etc.
 #[non_exhaustive]
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Method {
     #[allow(missing_docs)]
     GET,
@@ -37,6 +69,21 @@ pub enum Method {
     HEAD,
 }
 
+impl Method {
+    /// The method as it appears on the wire e.g. `GET`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::GET => "GET",
+            Self::POST => "POST",
+            Self::PUT => "PUT",
+            Self::PATCH => "PATCH",
+            Self::DELETE => "DELETE",
+            Self::OPTIONS => "OPTIONS",
+            Self::HEAD => "HEAD",
+        }
+    }
+}
+
 impl TryFrom<&[u8]> for Method {
     type Error = &'static str;
 
@@ -54,14 +101,57 @@ impl TryFrom<&[u8]> for Method {
     }
 }
 
+/// HTTP protocol version spoken by the client
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Version {
+    /// HTTP/1.0
+    Http10,
+    /// HTTP/1.1
+    Http11,
+}
+
+impl Version {
+    /// The version as it appears on the wire e.g. `HTTP/1.1`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Http10 => "HTTP/1.0",
+            Self::Http11 => "HTTP/1.1",
+        }
+    }
+
+    fn parse(value: &[u8]) -> Result<Self, RequestError> {
+        match value {
+            HTTP_1_0 => Ok(Self::Http10),
+            HTTP_1_1 => Ok(Self::Http11),
+            // later HTTP/1.x minor versions are backwards compatible with HTTP/1.1
+            [b'H', b'T', b'T', b'P', b'/', b'1', b'.', minor] if minor.is_ascii_digit() => {
+                Ok(Self::Http11)
+            }
+            [b'H', b'T', b'T', b'P', b'/', major, b'.', minor]
+                if major.is_ascii_digit() && minor.is_ascii_digit() =>
+            {
+                Err(RequestError::UnsupportedVersion)
+            }
+            _ => Err(RequestError::ProtocolError("malformed http version")),
+        }
+    }
+}
+
 /// Request comtains the details of the request parsed from bytes read from the client
 #[non_exhaustive]
 #[derive(Debug)]
 pub struct Request<'a> {
     /// Method (GET, POST etc) parsed from the request
     pub method: Method,
-    /// URL path e.g. `/index.html?foo=bar`
+    /// The request target e.g. `/index.html?foo=bar`, with any `.` and `..` path segments removed.
+    /// This includes the query, so handlers matching on the path alone should use `target.path()`.
     pub path: &'a str,
+    /// The request target split into its path and query
+    pub target: Target<'a>,
+    /// HTTP version from the request line
+    pub version: Version,
     /// Host extracted from the host header
     pub host: &'a str,
     /// Content-Type extracted from the Content-Type header where present
@@ -72,94 +162,243 @@ pub struct Request<'a> {
     pub content_length: usize,
     pub(crate) body: Option<&'a [u8]>,
     pub(crate) header_slice: Option<&'a [u8]>,
+    pub(crate) keep_alive: bool,
+    pub(crate) length: usize,
+    pub(crate) expect: Option<&'a str>,
+    pub(crate) body_remaining: usize,
+    pub(crate) body_framing: Option<BodyFraming>,
 }
 
 impl<'a> Request<'a> {
-    /// Parse the provided byte slice returning a Request or an error.
-    pub(crate) fn parse(data: &'a [u8]) -> Result<Self, RequestError> {
-        // ensure upfront we have valid utf8 so later we can just unwrap str conversions
-        if str::from_utf8(data).is_err() {
+    /// Parse the provided byte slice returning a Request or an error.  The request must be within
+    /// the limits of the provided config.  A body that would not fit within `capacity` bytes
+    /// (along with the request line and headers) is not waited for, the request is returned with
+    /// only the part of the body already received, leaving the remainder to be read from the
+    /// client.  A chunked body received in full is decoded in place.
+    pub(crate) fn parse(
+        data: &'a mut [u8],
+        capacity: usize,
+        limits: &ServerConfig,
+    ) -> Result<Self, RequestError> {
+        // ensure upfront the request line and headers are valid utf8 so later we can just unwrap
+        // str conversions.  The body may be binary, and a multi-byte character may be split by
+        // the end of the data received so far.
+        let head_len = data
+            .windows(4)
+            .position(|w| w == [CR, LF, CR, LF])
+            .map_or(data.len(), |p| p + 4);
+        if let Err(e) = str::from_utf8(&data[..head_len])
+            && e.error_len().is_some()
+        {
             return Err(RequestError::ProtocolError(
                 "http request is not valid utf8",
             ));
         }
 
+        // the request line is kept apart from the headers so the target can be normalized in
+        // place once the request is complete.  Until then the data is left untouched, as the
+        // request is parsed again when more arrives.
+        let (head, rest) = data.split_at_mut(head_len);
+        let line_len = head
+            .windows(2)
+            .position(|w| w == [CR, LF])
+            .map_or(0, |p| p + 2);
+        let (line, head) = head.split_at_mut(line_len);
+        let head: &'a [u8] = head;
+
         let mut req = Request {
             method: Method::GET,
             path: "",
+            target: Target::default(),
+            version: Version::Http11,
             host: "",
             content_type: None,
             user_agent: None,
             content_length: 0,
             header_slice: None,
             body: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
         };
 
-        let mut request_line_done = false;
+        let request_line_done = line_len > 0;
+        let mut target = 0..0;
+        if request_line_done {
+            if line_len > limits.max_request_line {
+                return Err(RequestError::UriTooLong);
+            }
+            target = req.parse_request_line(&line[..line_len - 2])?;
+        }
+
         let mut http_headers_done = false;
-        let mut header_start_offset = 0usize;
+        let mut header_start_offset = None;
         let mut header_end_offset = 0usize;
+        let mut header_count = 0usize;
 
+        // offsets in the loop are from the end of the request line
         let mut line_start = 0;
-        for i in 0..=data.len() {
-            if let [CR, LF] = &data[line_start..i] {
+        for i in 0..=head.len() {
+            if let [CR, LF] = &head[line_start..i] {
                 // a \r\n imediately after a line\r\n indicates the end of the headers
                 http_headers_done = true;
 
+                // being the first blank line, this is the end of `head`, and the body and any
+                // following requests are in `rest`
+                if !request_line_done {
+                    return Err(RequestError::ProtocolError("malformed HTTP request"));
+                }
+
+                if i > limits.max_header_bytes {
+                    return Err(RequestError::HeadersTooLarge);
+                }
+
+                // HTTP/1.0 clients do not understand interim responses so cannot expect one
+                let expect_continue = match req.expect {
+                    _ if req.version == Version::Http10 => false,
+                    Some(e) if e.eq_ignore_ascii_case("100-continue") => true,
+                    Some(_) => return Err(RequestError::ExpectationFailed),
+                    None => false,
+                };
+
+                if req.content_length > limits.max_body {
+                    return match expect_continue {
+                        true => Err(RequestError::ExpectationFailed),
+                        false => Err(RequestError::BodyTooLarge),
+                    };
+                }
+
+                // once the client starts sending the body it no longer waits
+                let awaiting_continue = expect_continue && rest.is_empty();
+
+                if req.body_framing == Some(BodyFraming::Chunked) {
+                    match ChunkedDecoder::decode_in_place(rest)
+                        .map_err(RequestError::ProtocolError)?
+                    {
+                        Some((consumed, len)) => {
+                            if len > limits.max_body {
+                                return Err(RequestError::BodyTooLarge);
+                            }
+                            let rest: &'a [u8] = rest;
+                            req.content_length = len;
+                            req.body = Some(&rest[..len]);
+                            req.length = line_len + i + consumed;
+                        }
+                        // the buffer is full, so the remainder is decoded as it is streamed to
+                        // the handler
+                        None if line_len + i + rest.len() >= capacity => {
+                            if !awaiting_continue {
+                                req.expect = None;
+                            }
+                            req.body = Some(rest);
+                            req.body_remaining = usize::MAX;
+                            req.length = line_len + i + rest.len();
+                        }
+                        None if awaiting_continue => return Err(RequestError::ExpectsContinue(0)),
+                        None => return Err(RequestError::Incomplete(Some(0))),
+                    }
+                    break;
+                }
+
+                if (line_len + i).saturating_add(req.content_length) > capacity {
+                    // the body is streamed to the handler, which must be told to continue if the
+                    // client is waiting for a 100 Continue before sending it.
+                    if !awaiting_continue {
+                        req.expect = None;
+                    }
+                    req.body = Some(rest);
+                    req.body_remaining = req.content_length - rest.len();
+                    req.length = line_len + i + rest.len();
+                    break;
+                }
+
                 if req.content_length > 0 {
-                    req.body = data.get(i..i + req.content_length);
+                    let rest: &'a [u8] = rest;
+                    req.body = rest.get(..req.content_length);
                     if req.body.is_none() {
+                        if awaiting_continue {
+                            return Err(RequestError::ExpectsContinue(req.content_length));
+                        }
                         return Err(RequestError::Incomplete(Some(req.content_length)));
                     }
                 }
 
+                req.length = line_len + i + req.content_length;
                 break;
             }
 
-            if let [line @ .., CR, LF] = &data[line_start..i] {
-                if !request_line_done {
-                    req.parse_request_line(line)?;
-                    request_line_done = true;
-                } else {
-                    req.parse_header_line(line)?;
-                    if header_start_offset == 0 {
-                        header_start_offset = line_start;
-                    }
-                    header_end_offset = i;
+            if let [line @ .., CR, LF] = &head[line_start..i] {
+                header_count += 1;
+                if header_count > limits.max_headers || i > limits.max_header_bytes {
+                    return Err(RequestError::HeadersTooLarge);
                 }
+                req.parse_header_line(line)?;
+                header_start_offset.get_or_insert(line_start);
+                header_end_offset = i;
                 line_start = i;
             }
         }
 
-        if header_start_offset != 0 && header_end_offset != 0 {
-            req.header_slice = Some(&data[header_start_offset..header_end_offset])
+        if let Some(header_start_offset) = header_start_offset {
+            req.header_slice = Some(&head[header_start_offset..header_end_offset])
         }
 
         if !http_headers_done {
+            if !request_line_done && head.len() >= limits.max_request_line {
+                return Err(RequestError::UriTooLong);
+            }
+            if request_line_done && head.len() > limits.max_header_bytes {
+                return Err(RequestError::HeadersTooLarge);
+            }
             return Err(RequestError::Incomplete(None));
         }
 
-        if req.path.is_empty() {
+        if target.is_empty() {
             return Err(RequestError::ProtocolError("malformed HTTP request"));
         }
 
+        let len = uri::normalize(&mut line[target.clone()]).map_err(RequestError::ProtocolError)?;
+        let line: &'a [u8] = line;
+        req.path = str::from_utf8(&line[target.start..target.start + len]).unwrap();
+        req.target = Target::parse(req.path);
+
+        // authority-form is only used by CONNECT, which is not supported, and asterisk-form only
+        // by OPTIONS
+        if req.target.authority().is_some() && req.target.path().is_empty() {
+            return Err(RequestError::ProtocolError(
+                "authority-form target requires CONNECT",
+            ));
+        }
+        if req.target.path() == "*" && req.method != Method::OPTIONS {
+            return Err(RequestError::ProtocolError(
+                "asterisk-form target requires OPTIONS",
+            ));
+        }
+
         Ok(req)
     }
 
-    fn parse_request_line(&mut self, data: &'a [u8]) -> Result<(), RequestError> {
-        for (i, word) in data.splitn(3, |b: &u8| *b == SP).enumerate() {
-            match i {
-                0 => match Method::try_from(word) {
-                    Ok(m) => self.method = m,
-                    Err(_) => return Err(RequestError::ProtocolError("unknown http method")),
-                },
-                1 => self.path = str::from_utf8(word).unwrap(),
-                2 => {}
-                _ => return Err(RequestError::ProtocolError("malformed http request")),
-            };
-        }
+    /// Parse the method and version from the request line, returning the position of the target
+    /// in the line.
+    fn parse_request_line(&mut self, data: &[u8]) -> Result<Range<usize>, RequestError> {
+        let mut words = data.split(|b: &u8| *b == SP);
+        let (Some(method), Some(target), Some(version), None) =
+            (words.next(), words.next(), words.next(), words.next())
+        else {
+            return Err(RequestError::ProtocolError("malformed http request"));
+        };
 
-        Ok(())
+        self.method =
+            Method::try_from(method).or(Err(RequestError::ProtocolError("unknown http method")))?;
+        self.version = Version::parse(version)?;
+        // HTTP/1.1 connections persist by default, HTTP/1.0 connections do not.  Either may be
+        // overridden by a Connection header.
+        self.keep_alive = self.version == Version::Http11;
+
+        let start = method.len() + 1;
+        Ok(start..start + target.len())
     }
 
     fn parse_header_line(&mut self, data: &'a [u8]) -> Result<(), RequestError> {
@@ -184,9 +423,38 @@ impl<'a> Request<'a> {
             match RequestHeader::try_from((header, value)) {
                 Ok(h) => {
                     if let RequestHeader::ContentLength(l) = h {
+                        // a repeated Content-Length must agree, or the request could be read
+                        // with either length
+                        match self.body_framing {
+                            Some(BodyFraming::Chunked) => {
+                                return Err(RequestError::ProtocolError(AMBIGUOUS_LENGTH));
+                            }
+                            Some(BodyFraming::ContentLength) if self.content_length != l => {
+                                return Err(RequestError::ProtocolError(AMBIGUOUS_LENGTH));
+                            }
+                            _ => {}
+                        }
+                        self.body_framing = Some(BodyFraming::ContentLength);
                         self.content_length = l;
                         return Ok(());
                     }
+                    if let RequestHeader::TransferEncoding(s) = h {
+                        if self.body_framing == Some(BodyFraming::ContentLength) {
+                            return Err(RequestError::ProtocolError(AMBIGUOUS_LENGTH));
+                        }
+                        // chunked must be the final encoding for the length to be known
+                        match s.rsplit(',').next() {
+                            Some(e) if e.trim().eq_ignore_ascii_case("chunked") => {
+                                self.body_framing = Some(BodyFraming::Chunked);
+                            }
+                            _ => {
+                                return Err(RequestError::ProtocolError(
+                                    "unsupported transfer-encoding",
+                                ));
+                            }
+                        }
+                        return Ok(());
+                    }
                     if let RequestHeader::Host(s) = h {
                         self.host = s;
                         return Ok(());
@@ -199,6 +467,18 @@ impl<'a> Request<'a> {
                         self.user_agent = Some(s);
                         return Ok(());
                     }
+                    if let RequestHeader::Expect(s) = h {
+                        self.expect = Some(s);
+                        return Ok(());
+                    }
+                    if let RequestHeader::Connection(s) = h {
+                        if has_token(s, "close") {
+                            self.keep_alive = false;
+                        } else if has_token(s, "keep-alive") {
+                            self.keep_alive = true;
+                        }
+                        return Ok(());
+                    }
 
                     return Ok(());
                 }
@@ -214,76 +494,160 @@ impl<'a> Request<'a> {
         Ok(())
     }
 
-    fn resolve_header(&self, data: &'a [u8]) -> Result<Option<RequestHeader<'a>>, RequestError> {
-        let mut header: Option<&'a str> = None;
-        let mut value: Option<&'a str> = None;
-
-        for (i, word) in data.splitn(2, |b: &u8| *b == COLON).enumerate() {
-            match i {
-                0 => {
-                    header = Some(str::from_utf8(word).unwrap().trim());
-                }
-                1 => {
-                    value = Some(str::from_utf8(word).unwrap().trim());
-                }
-                _ => return Err(RequestError::ProtocolError("malformed http request")),
-            }
-        }
-
-        if let Some(header) = header
-            && let Some(value) = value
-        {
-            match RequestHeader::try_from((header, value)) {
-                Ok(h) => {
-                    return Ok(Some(h));
-                }
-                Err(None) => {
-                    return Ok(None);
-                }
-                Err(Some(e)) => {
-                    return Err(RequestError::ProtocolError(e));
-                }
-            }
-        }
-
-        Ok(None)
-    }
-
     /// Search the portion of the oringinal byte slice that contained headers for a header matching
     /// the provided variant.
     /// Note: A number of header values are extracted during the initial parse which should be used
     /// in favor of this method which requireds a scan of the original headers each call.
     pub fn get_header(&self, header: RequestHeader<'_>) -> Option<RequestHeader<'a>> {
-        if let Some(data) = self.header_slice {
-            let mut line_start = 0;
-
-            for i in 0..=data.len() {
-                if let [line @ .., CR, LF] = &data[line_start..i] {
-                    if let Ok(Some(h)) = self.resolve_header(line) {
-                        match (header, h) {
-                            (RequestHeader::Other(key1, _), RequestHeader::Other(key2, _))
-                                if key1.eq_ignore_ascii_case(key2) =>
-                            {
-                                return Some(h);
-                            }
-                            (RequestHeader::Other(_, _), RequestHeader::Other(_, _)) => {}
-                            (h1, h2) if discriminant(&h1) == discriminant(&h2) => {
-                                return Some(h);
-                            }
-                            _ => {}
-                        };
-                    }
-                    line_start = i;
+        self.headers()
+            .filter_map(|pair| RequestHeader::try_from(pair).ok())
+            .find(|h| match (header, h) {
+                (RequestHeader::Other(key1, _), RequestHeader::Other(key2, _)) => {
+                    key1.eq_ignore_ascii_case(key2)
                 }
-            }
+                (h1, h2) => discriminant(&h1) == discriminant(h2),
+            })
+    }
+
+    /// Returns an iterator over the `(name, value)` pairs of all the request's headers in the order
+    /// they were sent, including repeated headers and those without a `RequestHeader` variant.
+    pub fn headers(&self) -> Headers<'a> {
+        // the headers were validated as utf8 when parsed
+        Headers::new(str::from_utf8(self.header_slice.unwrap_or_default()).unwrap())
+    }
+
+    /// Returns an iterator over the values of every header with the same name as `header`, with
+    /// comma separated lists split into their elements, e.g. `Accept: text/html, */*` yields
+    /// `text/html` then `*/*`.  Commas within quoted strings do not split a value.  The pairs of
+    /// Cookie headers are split at `;` instead.  Headers that are not lists, such as dates, should
+    /// be read with `get_header`.
+    ///
+    /// ```
+    /// # use weblite::header::RequestHeader;
+    /// # use weblite::request::Request;
+    /// fn forwarded_for<'a>(req: &Request<'a>) -> Option<&'a str> {
+    ///     // the client is the first of the addresses added by each proxy
+    ///     req.get_all(RequestHeader::Other("X-Forwarded-For", "")).next()
+    /// }
+    /// ```
+    pub fn get_all<'h>(&self, header: RequestHeader<'h>) -> HeaderValues<'a, 'h> {
+        let separator = match header {
+            RequestHeader::Cookie(_) => b';',
+            _ => b',',
         };
+        HeaderValues::new(self.headers(), header.name(), separator)
+    }
+
+    /// Returns an iterator over the `(name, value)` pairs of the cookies sent in the request's
+    /// Cookie headers.
+    pub fn cookies(&self) -> Cookies<'a> {
+        Cookies::new(self.get_all(RequestHeader::Cookie("")))
+    }
+
+    /// Returns the value of the first cookie named `name`, e.g. `req.cookie("session")`
+    pub fn cookie(&self, name: &str) -> Option<&'a str> {
+        self.cookies()
+            .find(|(cookie, _)| *cookie == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the parameters of the query string in the request target, which are empty if the
+    /// target has no query.
+    pub fn query(&self) -> Query<'a> {
+        Query::new(self.target.query().unwrap_or(""))
+    }
 
-        None
+    /// Returns the fields of a form submitted in the body (`application/x-www-form-urlencoded`).
+    /// The fields are read like the parameters of a query string.  The request's Content-Type
+    /// must be that of a form, and the body must have been received in full into the buffer.
+    pub fn form(&self) -> Result<Query<'a>, FormError> {
+        if !self.content_type.is_some_and(is_form) {
+            return Err(FormError::UnsupportedContentType);
+        }
+        if self.body_remaining != 0 {
+            return Err(FormError::BodyNotBuffered);
+        }
+
+        let body = self.body.unwrap_or_default();
+        let body = str::from_utf8(body).map_err(|_| DecodeError::Malformed)?;
+        Ok(Query::new(body))
     }
 
-    /// Returns a reference to the request body bytes if any.
+    /// Deserializes a form submitted in the body (`application/x-www-form-urlencoded`) into a `T`,
+    /// as `form::from_form` does, decoding any percent-encoded values into `buf`.
+    #[cfg(feature = "serde")]
+    pub fn form_as<'de, T: serde::Deserialize<'de>>(
+        &self,
+        buf: &'de mut [u8],
+    ) -> Result<T, FormError>
+    where
+        'a: 'de,
+    {
+        crate::form::from_form(self.form()?, buf)
+    }
+
+    /// Deserializes a JSON body received in full into the buffer, e.g. `req.json::<Settings>()`.
+    /// The request's Content-Type must be `application/json`.  Use `Request::json_from` for a
+    /// body that may not fit in the buffer.  `JsonError::status` gives the status of an
+    /// appropriate response to an error.
+    #[cfg(feature = "json")]
+    pub fn json<T: serde::Deserialize<'a>>(&self) -> Result<T, JsonError> {
+        if !self.content_type.is_some_and(json::is_json) {
+            return Err(JsonError::UnsupportedContentType);
+        }
+        if self.body_remaining != 0 {
+            return Err(JsonError::BodyNotBuffered);
+        }
+        json::from_body(self.body.unwrap_or_default())
+    }
+
+    /// Reads a JSON body from `reader` into `buf` and deserializes it.  This allows bodies larger
+    /// than the server's buffer to be read with the `BodyReader` from `Responder::body_reader()`.
+    /// The request's Content-Type must be `application/json`.
+    #[cfg(feature = "json")]
+    pub async fn json_from<'b, T: serde::Deserialize<'b>, R: Read>(
+        &self,
+        reader: R,
+        buf: &'b mut [u8],
+    ) -> Result<T, JsonError> {
+        if !self.content_type.is_some_and(json::is_json) {
+            return Err(JsonError::UnsupportedContentType);
+        }
+        json::from_reader(reader, buf).await
+    }
+
+    /// Returns a parser for a `multipart/form-data` body, such as a file uploaded from a browser.
+    /// The body is read from `reader`, usually the `BodyReader` from `Responder::body_reader()`,
+    /// and `buf` holds the headers of each part while its body is streamed.  The request's
+    /// Content-Type must be that of multipart form data, giving the boundary between parts.
+    pub fn multipart<'m, R: Read>(
+        &self,
+        reader: R,
+        buf: &'m mut [u8],
+    ) -> Result<Multipart<'m, R>, MultipartError>
+    where
+        'a: 'm,
+    {
+        match self.content_type.and_then(boundary) {
+            Some(boundary) => Ok(Multipart::new(boundary, reader, buf)),
+            None => Err(MultipartError::UnsupportedContentType),
+        }
+    }
+
+    /// Returns a reference to the request body bytes if any.  Returns `None` if the body did not
+    /// fit in the server's buffer, in which case it must be read with the `BodyReader` provided
+    /// by `Responder::body_reader()`.
     pub fn get_body(&self) -> Option<&'a [u8]> {
-        self.body
+        match self.body_remaining {
+            0 => self.body,
+            _ => None,
+        }
+    }
+
+    /// Returns true if the client expects the connection to remain open after the response to
+    /// this request, as determined by the Connection header.
+    pub fn keep_alive(&self) -> bool {
+        self.keep_alive
     }
 }
 
@@ -293,26 +657,291 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_http_request_parsing_target() {
+        // the target is normalized in the buffer only once the request is complete, so parsing
+        // an incomplete request again as more arrives gives the same target
+        let mut req = "GET /static/./css/../app.js?v=1#top HTTP/1.1\r\nHost: device\r\n"
+            .as_bytes()
+            .to_vec();
+        assert!(matches!(
+            Request::parse(&mut req, usize::MAX, &ServerConfig::default()),
+            Err(RequestError::Incomplete(None))
+        ));
+        req.extend_from_slice(b"Content-Length: 2\r\n\r\n");
+        assert!(matches!(
+            Request::parse(&mut req, usize::MAX, &ServerConfig::default()),
+            Err(RequestError::Incomplete(Some(2)))
+        ));
+        req.extend_from_slice(b"ok");
+
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
+        assert_eq!(req.path, "/static/app.js?v=1");
+        assert_eq!(req.target.path(), "/static/app.js");
+        assert_eq!(req.target.query(), Some("v=1"));
+        assert_eq!(req.query().get_parsed::<u32>("v"), Some(Ok(1)));
+        assert_eq!(req.target.authority(), None);
+        assert_eq!(req.version, Version::Http11);
+        assert_eq!(req.host, "device");
+        assert_eq!(req.get_body(), Some("ok".as_bytes()));
+
+        let mut req = "GET http://device:8080/status HTTP/1.1\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
+        assert_eq!(req.target.authority(), Some("device:8080"));
+        assert_eq!(req.target.path(), "/status");
+
+        let mut req = "OPTIONS * HTTP/1.1\r\n\r\n".as_bytes().to_vec();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
+        assert_eq!(req.target.path(), "*");
+
+        for req in [
+            "GET /../secret HTTP/1.1\r\n\r\n",
+            "GET /a/%2e%2e/%2E%2E/secret HTTP/1.1\r\n\r\n",
+            "GET /file%00.txt HTTP/1.1\r\n\r\n",
+            "GET / HTTP/1.1 extra\r\n\r\n",
+            "GET /\r\n\r\n",
+            "GET  /  HTTP/1.1\r\n\r\n",
+            "GET /  HTTP/1.1\r\n\r\n",
+            "GET / HTTP/1.1 \r\n\r\n",
+            " GET / HTTP/1.1\r\n\r\n",
+            "GET example.com:80 HTTP/1.1\r\n\r\n",
+            "OPTIONS example.com:80 HTTP/1.1\r\n\r\n",
+            "GET * HTTP/1.1\r\n\r\n",
+        ] {
+            let mut buf = req.as_bytes().to_vec();
+            assert!(
+                matches!(
+                    Request::parse(&mut buf, usize::MAX, &ServerConfig::default()),
+                    Err(RequestError::ProtocolError(_))
+                ),
+                "{req}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_http_request_headers() {
+        let mut req = "GET / HTTP/1.1\r\nHost: device\r\n\
+            Accept: text/html, application/json;q=0.9\r\n\
+            X-Forwarded-For: 203.0.113.7\r\n\
+            accept: */*;q=0.1 ,\r\n\
+            If-None-Match: \"v1,2\", W/\"v\\\"3\"\r\n\
+            x-forwarded-for: 10.0.0.1, 10.0.0.2\r\n\
+            Cookie: a=1; b=2\r\nCookie: c=3\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
+
+        let headers = req.headers().collect::<std::vec::Vec<_>>();
+        assert_eq!(headers.len(), 8);
+        assert_eq!(headers[0], ("Host", "device"));
+        assert_eq!(headers[3], ("accept", "*/*;q=0.1 ,"));
+        assert_eq!(
+            RequestHeader::try_from(headers[1]),
+            Ok(RequestHeader::Accept("text/html, application/json;q=0.9"))
+        );
+
+        assert_eq!(
+            req.get_all(RequestHeader::Accept(""))
+                .collect::<std::vec::Vec<_>>(),
+            ["text/html", "application/json;q=0.9", "*/*;q=0.1"]
+        );
+        assert_eq!(
+            req.get_all(RequestHeader::Other("X-Forwarded-For", ""))
+                .collect::<std::vec::Vec<_>>(),
+            ["203.0.113.7", "10.0.0.1", "10.0.0.2"]
+        );
+        assert_eq!(
+            req.get_all(RequestHeader::IfNoneMatch(""))
+                .collect::<std::vec::Vec<_>>(),
+            ["\"v1,2\"", "W/\"v\\\"3\""]
+        );
+        assert_eq!(
+            req.get_all(RequestHeader::Cookie(""))
+                .collect::<std::vec::Vec<_>>(),
+            ["a=1", "b=2", "c=3"]
+        );
+        assert_eq!(req.get_all(RequestHeader::Referer("")).count(), 0);
+
+        // get_header still finds the first
+        assert_eq!(
+            req.get_header(RequestHeader::Other("x-forwarded-for", "")),
+            Some(RequestHeader::Other("X-Forwarded-For", "203.0.113.7"))
+        );
+    }
+
+    #[test]
+    fn test_http_request_cookies() {
+        let mut req = "GET /admin HTTP/1.1\r\nCookie: session=31d4d96e; theme=dark\r\n\
+            Host: device\r\nCookie: lang=en\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
+        assert_eq!(req.cookie("session"), Some("31d4d96e"));
+        assert_eq!(req.cookie("lang"), Some("en"));
+        assert_eq!(req.cookie("missing"), None);
+        assert_eq!(req.cookies().count(), 3);
+        assert_eq!(
+            req.get_header(RequestHeader::Cookie("")),
+            Some(RequestHeader::Cookie("session=31d4d96e; theme=dark"))
+        );
+
+        let mut req = "GET / HTTP/1.1\r\n\r\n".as_bytes().to_vec();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
+        assert_eq!(req.cookies().count(), 0);
+    }
+
+    #[test]
+    fn test_http_request_form() {
+        let mut req = "POST /settings HTTP/1.1\r\n\
+            Content-Type: application/x-www-form-urlencoded; charset=UTF-8\r\n\
+            Content-Length: 26\r\n\r\nname=my+device&interval=30"
+            .as_bytes()
+            .to_vec();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
+        let form = req.form().unwrap();
+        let mut buf = [0u8; 16];
+        assert_eq!(form.get_decoded("name", &mut buf), Some(Ok("my device")));
+        assert_eq!(form.get_parsed::<u32>("interval"), Some(Ok(30)));
+
+        let mut req = "POST /settings HTTP/1.1\r\nContent-Type: application/json\r\n\
+            Content-Length: 2\r\n\r\n{}"
+            .as_bytes()
+            .to_vec();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
+        assert_eq!(req.form(), Err(FormError::UnsupportedContentType));
+
+        // a body that does not fit in the buffer is left to be streamed
+        let mut req = "POST /settings HTTP/1.1\r\n\
+            Content-Type: application/x-www-form-urlencoded\r\n\
+            Content-Length: 100\r\n\r\nname=my"
+            .as_bytes()
+            .to_vec();
+        let req = Request::parse(&mut req, 120, &ServerConfig::default()).unwrap();
+        assert_eq!(req.form(), Err(FormError::BodyNotBuffered));
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn test_http_request_json() {
+        #[derive(Debug, serde::Deserialize, PartialEq)]
+        struct Led<'a> {
+            colour: &'a str,
+            on: bool,
+        }
+
+        let mut req = "PUT /led HTTP/1.1\r\nContent-Type: application/json\r\n\
+            Content-Length: 29\r\n\r\n{\"colour\": \"red\", \"on\": true}"
+            .as_bytes()
+            .to_vec();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
+        let led = Led {
+            colour: "red",
+            on: true,
+        };
+        assert_eq!(req.json::<Led>(), Ok(led));
+
+        let mut buf = [0u8; 32];
+        let body = req.get_body().unwrap();
+        assert_eq!(
+            req.json_from::<Led, _>(body, &mut buf).await,
+            Ok(Led {
+                colour: "red",
+                on: true
+            })
+        );
+
+        let mut req = "PUT /led HTTP/1.1\r\nContent-Type: text/plain\r\n\
+            Content-Length: 2\r\n\r\n{}"
+            .as_bytes()
+            .to_vec();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
+        let err = req.json::<Led>().unwrap_err();
+        assert_eq!(err, JsonError::UnsupportedContentType);
+        assert_eq!(
+            err.status(),
+            crate::response::StatusCode::UnsupportedMediaType
+        );
+
+        let mut req = "PUT /led HTTP/1.1\r\nContent-Type: application/json\r\n\
+            Content-Length: 13\r\n\r\n{\"colour\": 1}"
+            .as_bytes()
+            .to_vec();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
+        let err = req.json::<Led>().unwrap_err();
+        assert_eq!(err.status(), crate::response::StatusCode::BadRequest);
+
+        // only whitespace may follow the value
+        for body in [
+            "{\"colour\": \"red\", \"on\": true} \r\n",
+            "{\"colour\": \"red\", \"on\": true}x",
+            "{\"colour\": \"red\", \"on\": true} x",
+        ] {
+            let mut req = std::format!(
+                "PUT /led HTTP/1.1\r\nContent-Type: application/json\r\n\
+                Content-Length: {}\r\n\r\n{body}",
+                body.len()
+            )
+            .into_bytes();
+            let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
+            assert_eq!(req.json::<Led>().is_ok(), body.ends_with('\n'), "{body}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_request_multipart() {
+        let mut req = "POST /upload HTTP/1.1\r\n\
+            Content-Type: multipart/form-data; boundary=b1\r\n\
+            Content-Length: 59\r\n\r\n\
+            --b1\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n--b1--\r\n"
+            .as_bytes()
+            .to_vec();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
+        let mut buf = [0u8; 64];
+        let mut form = req.multipart(req.get_body().unwrap(), &mut buf).unwrap();
+        let mut part = form.next_part().await.unwrap().unwrap();
+        assert_eq!(part.name(), Some("a"));
+        let mut value = [0u8; 4];
+        assert_eq!(part.read(&mut value).await, Ok(1));
+        assert_eq!(value[0], b'1');
+        assert!(form.next_part().await.unwrap().is_none());
+
+        let mut req = "POST /upload HTTP/1.1\r\nContent-Type: multipart/form-data\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
+        assert!(matches!(
+            req.multipart(&b""[..], &mut buf),
+            Err(MultipartError::UnsupportedContentType)
+        ));
+    }
+
     #[test]
     fn test_http_request_parsing_single_receive() {
-        let req = "GET / HTTP/1.1\r\nContent-Length: 0\r\n\r\n".as_bytes();
+        let mut req = "GET / HTTP/1.1\r\nContent-Length: 0\r\n\r\n"
+            .as_bytes()
+            .to_vec();
 
-        let req = Request::parse(req).unwrap();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
         assert!(req.method == Method::GET);
         assert!(req.path == "/");
         assert!(req.content_length == 0, "{:?}", req);
 
-        let req = "GET /index.html HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc".as_bytes();
+        let mut req = "GET /index.html HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc"
+            .as_bytes()
+            .to_vec();
 
-        let req = Request::parse(req).unwrap();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
         assert!(req.method == Method::GET);
         assert!(req.path == "/index.html");
         assert!(req.content_length == 3, "{:?}", req);
         assert_eq!(req.get_body(), Some("abc".as_bytes()));
 
-        let req = "GET /index.html HTTP/1.1\r\ncontent-type: application/json\r\ncontent-length: 3\r\naccept: application/json\r\nAccept-Encoding: gzip\r\n\r\nabc".as_bytes();
+        let mut req = "GET /index.html HTTP/1.1\r\ncontent-type: application/json\r\ncontent-length: 3\r\naccept: application/json\r\nAccept-Encoding: gzip\r\n\r\nabc".as_bytes().to_vec();
 
-        let req = Request::parse(req).unwrap();
+        let req = Request::parse(&mut req, usize::MAX, &ServerConfig::default()).unwrap();
         assert!(req.method == Method::GET);
         assert!(req.path == "/index.html");
         assert!(req.content_length == 3, "{:?}", req);
@@ -338,12 +967,265 @@ mod tests {
         let req_part_one = "GET / HTTP/1.1\r\nContentType:".as_bytes();
         let req_part_two = "application/json\r\n\r\n".as_bytes();
 
-        http_buf[..req_part_one.len()].copy_from_slice(&req_part_one);
+        http_buf[..req_part_one.len()].copy_from_slice(req_part_one);
         http_buf[req_part_one.len()..req_part_one.len() + req_part_two.len()]
-            .copy_from_slice(&req_part_two);
+            .copy_from_slice(req_part_two);
 
-        let req = Request::parse(&http_buf[..]).unwrap();
+        let req = Request::parse(&mut http_buf[..], usize::MAX, &ServerConfig::default()).unwrap();
         assert!(req.method == Method::GET);
         assert!(req.path == "/");
     }
+
+    #[test]
+    fn test_http_request_parsing_length_and_keep_alive() {
+        let req = "GET / HTTP/1.1\r\nContent-Length: 3\r\n\r\nabcGET /next HTTP/1.1\r\n\r\n";
+
+        let mut buf = req.as_bytes().to_vec();
+        let parsed = Request::parse(&mut buf, usize::MAX, &ServerConfig::default()).unwrap();
+        assert_eq!(parsed.length, req.find("GET /next").unwrap());
+        assert!(parsed.keep_alive());
+
+        let req = "GET / HTTP/1.1\r\nConnection: Close\r\n\r\n";
+        let mut buf = req.as_bytes().to_vec();
+        let parsed = Request::parse(&mut buf, usize::MAX, &ServerConfig::default()).unwrap();
+        assert_eq!(parsed.length, req.len());
+        assert!(!parsed.keep_alive());
+    }
+
+    #[test]
+    fn test_http_request_parsing_version() {
+        let config = ServerConfig::default();
+
+        let mut buf = "GET / HTTP/1.1\r\n\r\n".as_bytes().to_vec();
+        let req = Request::parse(&mut buf, usize::MAX, &config).unwrap();
+        assert_eq!(req.version, Version::Http11);
+        assert!(req.keep_alive());
+
+        let mut buf = "GET / HTTP/1.0\r\n\r\n".as_bytes().to_vec();
+        let req = Request::parse(&mut buf, usize::MAX, &config).unwrap();
+        assert_eq!(req.version, Version::Http10);
+        assert!(!req.keep_alive());
+
+        let mut buf = "GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let req = Request::parse(&mut buf, usize::MAX, &config).unwrap();
+        assert_eq!(req.version, Version::Http10);
+        assert!(req.keep_alive());
+
+        assert_eq!(
+            Request::parse(
+                &mut "GET / HTTP/2.0\r\n\r\n".as_bytes().to_vec(),
+                usize::MAX,
+                &config
+            )
+            .unwrap_err(),
+            RequestError::UnsupportedVersion
+        );
+        assert!(matches!(
+            Request::parse(
+                &mut "GET / FTP\r\n\r\n".as_bytes().to_vec(),
+                usize::MAX,
+                &config
+            ),
+            Err(RequestError::ProtocolError(_))
+        ));
+        assert!(matches!(
+            Request::parse(
+                &mut "GET /\r\n\r\n".as_bytes().to_vec(),
+                usize::MAX,
+                &config
+            ),
+            Err(RequestError::ProtocolError(_))
+        ));
+    }
+
+    #[test]
+    fn test_http_request_parsing_limits() {
+        let config = ServerConfig {
+            max_request_line: 24,
+            max_headers: 2,
+            max_header_bytes: 40,
+            max_body: 4,
+        };
+
+        let req = "GET /ok HTTP/1.1\r\nA: 1\r\nB: 2\r\nContent-Length: 4\r\n\r\nabcd";
+        assert!(
+            Request::parse(
+                &mut req.as_bytes().to_vec(),
+                usize::MAX,
+                &ServerConfig::default()
+            )
+            .is_ok()
+        );
+        let req = "GET /ok HTTP/1.1\r\nA: 1\r\nContent-Length: 4\r\n\r\nabcd";
+        assert!(Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config).is_ok());
+
+        // limits are detected before the request is complete
+        let req = "GET /a/very/long/path/indeed HTTP/1.1\r\n\r\n";
+        assert_eq!(
+            Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config).unwrap_err(),
+            RequestError::UriTooLong
+        );
+        assert_eq!(
+            Request::parse(&mut req.as_bytes()[..24].to_vec(), usize::MAX, &config).unwrap_err(),
+            RequestError::UriTooLong
+        );
+
+        let req = "GET /ok HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n";
+        assert_eq!(
+            Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config).unwrap_err(),
+            RequestError::HeadersTooLarge
+        );
+
+        let req = "GET /ok HTTP/1.1\r\nX-Long: 0123456789012345678901234567890123456789";
+        assert_eq!(
+            Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config).unwrap_err(),
+            RequestError::HeadersTooLarge
+        );
+
+        let req = "GET /ok HTTP/1.1\r\nContent-Length: 5\r\n\r\nab";
+        assert_eq!(
+            Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config).unwrap_err(),
+            RequestError::BodyTooLarge
+        );
+    }
+
+    #[test]
+    fn test_http_request_parsing_expect() {
+        let config = ServerConfig {
+            max_body: 8,
+            ..ServerConfig::default()
+        };
+
+        let req = "POST / HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 3\r\n\r\n";
+        assert_eq!(
+            Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config).unwrap_err(),
+            RequestError::ExpectsContinue(3)
+        );
+
+        // the client has started sending the body without waiting
+        let req = "POST / HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 3\r\n\r\na";
+        assert_eq!(
+            Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config).unwrap_err(),
+            RequestError::Incomplete(Some(3))
+        );
+
+        let req = "POST / HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 3\r\n\r\nabc";
+        assert!(Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config).is_ok());
+
+        let req = "POST / HTTP/1.0\r\nExpect: 100-continue\r\nContent-Length: 3\r\n\r\n";
+        assert_eq!(
+            Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config).unwrap_err(),
+            RequestError::Incomplete(Some(3))
+        );
+
+        let req = "POST / HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 9\r\n\r\n";
+        assert_eq!(
+            Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config).unwrap_err(),
+            RequestError::ExpectationFailed
+        );
+
+        let req = "POST / HTTP/1.1\r\nExpect: something-else\r\nContent-Length: 3\r\n\r\n";
+        assert_eq!(
+            Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config).unwrap_err(),
+            RequestError::ExpectationFailed
+        );
+    }
+
+    #[test]
+    fn test_http_request_parsing_streamed_body() {
+        let config = ServerConfig::default();
+
+        // the body would not fit in 64 bytes, so only the received part is returned
+        let req = "POST / HTTP/1.1\r\nContent-Length: 100\r\n\r\nabc".as_bytes();
+        let mut buf = req.to_vec();
+        let parsed = Request::parse(&mut buf, 64, &config).unwrap();
+        assert_eq!(parsed.body, Some("abc".as_bytes()));
+        assert_eq!(parsed.body_remaining, 97);
+        assert_eq!(parsed.length, req.len());
+        assert_eq!(parsed.get_body(), None);
+
+        // a body that fits is waited for
+        assert_eq!(
+            Request::parse(&mut req.to_vec(), 1024, &config).unwrap_err(),
+            RequestError::Incomplete(Some(100))
+        );
+
+        // bodies need not be utf8
+        let mut req = "POST / HTTP/1.1\r\nContent-Length: 2\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        req.extend_from_slice(&[0xff, 0xfe]);
+        let parsed = Request::parse(&mut req, 1024, &config).unwrap();
+        assert_eq!(parsed.get_body(), Some(&[0xff, 0xfe][..]));
+
+        let req = b"POST /\xff HTTP/1.1\r\n\r\n";
+        assert!(matches!(
+            Request::parse(&mut req.to_vec(), 1024, &config),
+            Err(RequestError::ProtocolError(_))
+        ));
+    }
+
+    #[test]
+    fn test_http_request_parsing_chunked() {
+        let config = ServerConfig {
+            max_body: 16,
+            ..ServerConfig::default()
+        };
+
+        let req = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4;a=b\r\nWiki\r\n5\r\npedia\r\n0\r\nX-Trailer: 1\r\n\r\nGET /next HTTP/1.1\r\n\r\n";
+        let mut buf = req.as_bytes().to_vec();
+        let parsed = Request::parse(&mut buf, usize::MAX, &config).unwrap();
+        assert_eq!(parsed.get_body(), Some("Wikipedia".as_bytes()));
+        assert_eq!(parsed.content_length, 9);
+        assert_eq!(parsed.length, req.find("GET /next").unwrap());
+
+        let req = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nWi";
+        assert_eq!(
+            Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config).unwrap_err(),
+            RequestError::Incomplete(Some(0))
+        );
+
+        // a body that would not fit is streamed still encoded
+        let req = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nWi";
+        let mut buf = req.as_bytes().to_vec();
+        let parsed = Request::parse(&mut buf, req.len(), &config).unwrap();
+        assert_eq!(parsed.body, Some("4\r\nWi".as_bytes()));
+        assert_eq!(parsed.body_framing, Some(BodyFraming::Chunked));
+        assert_eq!(parsed.get_body(), None);
+
+        let req = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n11\r\n01234567890123456\r\n0\r\n\r\n";
+        assert_eq!(
+            Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config).unwrap_err(),
+            RequestError::BodyTooLarge
+        );
+
+        // a repeated Content-Length is only accepted if the lengths agree
+        let req = "POST / HTTP/1.1\r\nContent-Length: 4\r\nContent-Length: 4\r\n\r\nWiki";
+        let mut buf = req.as_bytes().to_vec();
+        let parsed = Request::parse(&mut buf, usize::MAX, &config).unwrap();
+        assert_eq!(parsed.get_body(), Some("Wiki".as_bytes()));
+
+        let req = "POST / HTTP/1.1\r\nContent-Length: 4\r\nContent-Length: 14\r\n\r\nWiki";
+        assert_eq!(
+            Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config).unwrap_err(),
+            RequestError::ProtocolError(AMBIGUOUS_LENGTH)
+        );
+
+        for req in [
+            "POST / HTTP/1.1\r\nContent-Length: 4\r\nTransfer-Encoding: chunked\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 4\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nx\r\n",
+        ] {
+            assert!(
+                matches!(
+                    Request::parse(&mut req.as_bytes().to_vec(), usize::MAX, &config),
+                    Err(RequestError::ProtocolError(_))
+                ),
+                "{req}"
+            );
+        }
+    }
 }
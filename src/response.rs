@@ -1,12 +1,18 @@
-use embedded_io_async::{Read, Write};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
 
 use crate::ascii::{AsciiInt, CR, LF, SP};
-use crate::header::{RequestHeader, ResponseHeader};
-use crate::request::Request;
+use crate::chunked::{Chunk, ChunkedDecoder};
+use crate::header::{RESP_HEAD_CONTENT_LENGTH, RequestHeader, ResponseHeader};
+#[cfg(feature = "json")]
+use crate::json::APPLICATION_JSON;
+use crate::request::{BodyFraming, Method, Request, Version};
 use crate::websocket::{Websocket, sec_websocket_accept_val};
 use crate::{HttpWrite, WriteError};
 
-const HTTP_PROTO: &str = "HTTP/1.1";
+/// Number of sets of headers that can be added with `Responder::with_default_headers`
+const DEFAULT_HEADER_SETS: usize = 4;
 
 /// Responder error is returned as the error when responding to clients.  Generally users of the
 /// weblite library will not inspect this error, but pass it on from the handler implementations.
@@ -19,6 +25,15 @@ pub enum ResponderError {
     ProtocolError(&'static str),
 }
 
+impl embedded_io_async::Error for ResponderError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::NetworkError => ErrorKind::Other,
+            Self::ProtocolError(_) => ErrorKind::InvalidData,
+        }
+    }
+}
+
 impl From<WriteError> for ResponderError {
     fn from(value: WriteError) -> Self {
         match value {
@@ -29,39 +44,107 @@ impl From<WriteError> for ResponderError {
 
 /// HTTP status code returned in a response
 #[non_exhaustive]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum StatusCode {
+    /// 100 Continue
+    Continue,
     /// 101 Swithcing Protocols - eg from HTTP to Websocket
     SwitchingProtocols,
+    /// 103 Early Hints
+    EarlyHints,
     /// 200 Ok
     OK,
+    /// 204 No Content
+    NoContent,
     /// 400 Bad Request
     BadRequest,
     /// 404 Not Found
     NotFound,
+    /// 405 Method Not Allowed
+    MethodNotAllowed,
+    /// 408 Request Timeout
+    RequestTimeout,
+    /// 413 Content Too Large
+    ContentTooLarge,
+    /// 414 URI Too Long
+    UriTooLong,
+    /// 415 Unsupported Media Type
+    UnsupportedMediaType,
+    /// 417 Expectation Failed
+    ExpectationFailed,
+    /// 431 Request Header Fields Too Large
+    RequestHeaderFieldsTooLarge,
     /// 500 Server Error
     InternalServerError,
+    /// 505 HTTP Version Not Supported
+    HttpVersionNotSupported,
     /// Any other code
     Other(u16),
 }
 
-impl HttpWrite for StatusCode {
+impl StatusCode {
+    /// The numeric value of the status code.
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::Continue => 100,
+            Self::SwitchingProtocols => 101,
+            Self::EarlyHints => 103,
+            Self::OK => 200,
+            Self::NoContent => 204,
+            Self::BadRequest => 400,
+            Self::NotFound => 404,
+            Self::MethodNotAllowed => 405,
+            Self::RequestTimeout => 408,
+            Self::ContentTooLarge => 413,
+            Self::UriTooLong => 414,
+            Self::UnsupportedMediaType => 415,
+            Self::ExpectationFailed => 417,
+            Self::RequestHeaderFieldsTooLarge => 431,
+            Self::InternalServerError => 500,
+            Self::HttpVersionNotSupported => 505,
+            Self::Other(n) => *n,
+        }
+    }
+
+    /// Responses with informational (1xx), 204 No Content and 304 Not Modified statuses never
+    /// include a body, or a Content-Length header describing one.
+    fn permits_body(&self) -> bool {
+        !matches!(self.code(), 100..=199 | 204 | 304)
+    }
+}
+
+/// The first line of a response e.g. `HTTP/1.1 200 OK`
+struct StatusLine(Version, StatusCode);
+
+impl HttpWrite for StatusLine {
     #[rustfmt::skip]
     async fn write<T: Write>(self, writer: &mut T) -> Result<(), WriteError> {
         let other: AsciiInt;
-        let data = match self {
-            Self::SwitchingProtocols => "101 Switching Protocols",
-            Self::OK => "200 OK",
-            Self::BadRequest => "400 Bad Request",
-            Self::NotFound => "404 Not Found",
-            Self::InternalServerError => "500 Internal Server Error",
-            Self::Other(n) => {
+        let data = match self.1 {
+            StatusCode::Continue => "100 Continue",
+            StatusCode::SwitchingProtocols => "101 Switching Protocols",
+            StatusCode::EarlyHints => "103 Early Hints",
+            StatusCode::OK => "200 OK",
+            StatusCode::NoContent => "204 No Content",
+            StatusCode::BadRequest => "400 Bad Request",
+            StatusCode::NotFound => "404 Not Found",
+            StatusCode::MethodNotAllowed => "405 Method Not Allowed",
+            StatusCode::RequestTimeout => "408 Request Timeout",
+            StatusCode::ContentTooLarge => "413 Content Too Large",
+            StatusCode::UriTooLong => "414 URI Too Long",
+            StatusCode::UnsupportedMediaType => "415 Unsupported Media Type",
+            StatusCode::ExpectationFailed => "417 Expectation Failed",
+            StatusCode::RequestHeaderFieldsTooLarge => "431 Request Header Fields Too Large",
+            StatusCode::InternalServerError => "500 Internal Server Error",
+            StatusCode::HttpVersionNotSupported => "505 HTTP Version Not Supported",
+            StatusCode::Other(n) => {
                 other = AsciiInt::from(n as u64);
                 other.as_str()
             }
         };
 
-        writer.write_all(HTTP_PROTO.as_bytes()).await
+        writer.write_all(self.0.as_str().as_bytes()).await
             .and(writer.write_all(&[SP]).await
             .and(writer.write_all(data.as_bytes()).await
             .and(writer.write_all(&[CR, LF]).await
@@ -69,16 +152,188 @@ impl HttpWrite for StatusCode {
     }
 }
 
+/// Progress of a response, shared between the server and the `Responder` given to a handler so
+/// the server can tell what the handler sent once it has returned.
+#[derive(Default)]
+pub(crate) struct ResponseState {
+    status_sent: AtomicBool,
+    finished: AtomicBool,
+    body_unread: AtomicUsize,
+    continue_pending: AtomicBool,
+    upgraded: AtomicBool,
+    timed_out: AtomicBool,
+    route: AtomicUsize,
+}
+
+impl ResponseState {
+    /// Whether a status line has been written to the client.
+    pub(crate) fn status_sent(&self) -> bool {
+        self.status_sent.load(Ordering::Relaxed)
+    }
+
+    /// Whether the response has been completed with a body, no body, or a websocket upgrade.
+    pub(crate) fn finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes of the request body not yet read from the client.
+    pub(crate) fn body_unread(&self) -> usize {
+        self.body_unread.load(Ordering::Relaxed)
+    }
+
+    /// Whether the client is still waiting for a 100 Continue before sending the body.
+    pub(crate) fn continue_pending(&self) -> bool {
+        self.continue_pending.load(Ordering::Relaxed)
+    }
+
+    /// Whether the connection has been upgraded to a websocket.
+    pub(crate) fn upgraded(&self) -> bool {
+        self.upgraded.load(Ordering::Relaxed)
+    }
+
+    /// Whether a read of the request body timed out.
+    pub(crate) fn timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::Relaxed)
+    }
+
+    /// Record that a read of the request body timed out.
+    pub(crate) fn time_out(&self) {
+        self.timed_out.store(true, Ordering::Relaxed);
+    }
+
+    /// The choices recorded with `Responder::with_route` while the request was dispatched.
+    pub(crate) fn route(&self) -> usize {
+        self.route.load(Ordering::Relaxed)
+    }
+}
+
 struct ResponderInner<'a, 'client, C: Read + Write> {
+    state: Option<&'a ResponseState>,
     status: StatusCode,
     server: ResponseHeader<'a>,
     client: &'client mut C,
+    method: Method,
+    version: Version,
+    keep_alive: bool,
+    framed: bool,
+    // methods listed in an Allow header sent with the status
+    allow: Option<&'a [Method]>,
+    // headers added by `Responder::with_default_headers` sent with the status
+    default_headers: [&'a [ResponseHeader<'a>]; DEFAULT_HEADER_SETS],
+    // part of the request body already received but not yet read by the handler
+    body: &'a [u8],
+    // part of the request body yet to be received from the client
+    body_remaining: usize,
+    continue_pending: bool,
+    // decoder for a chunked body being streamed from the client
+    chunked: Option<ChunkedDecoder>,
+    // choices recorded by `Responder::with_route`, and the product of their counts
+    route: usize,
+    route_scale: usize,
 }
 
 impl<'a, 'client, C: Read + Write> ResponderInner<'a, 'client, C> {
+    /// Record that the response is complete.
+    fn finish(&self) {
+        if let Some(state) = self.state {
+            state.finished.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Record how much of the request body remains to be received.
+    fn record_body(&self) {
+        if let Some(state) = self.state {
+            state
+                .body_unread
+                .store(self.body_remaining, Ordering::Relaxed);
+            state
+                .continue_pending
+                .store(self.continue_pending, Ordering::Relaxed);
+        }
+    }
+
+    async fn read_body(&mut self, buf: &mut [u8]) -> Result<usize, ResponderError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            // the part of the body received with the request comes first
+            let n = if !self.body.is_empty() {
+                let n = buf.len().min(self.body.len());
+                buf[..n].copy_from_slice(&self.body[..n]);
+                self.body = &self.body[n..];
+                n
+            } else {
+                let wanted = match &self.chunked {
+                    Some(decoder) => decoder.wanted(),
+                    None => self.body_remaining,
+                };
+                if wanted == 0 {
+                    return Ok(0);
+                }
+
+                if self.continue_pending {
+                    self.continue_pending = false;
+                    self.record_body();
+                    self.informational(StatusCode::Continue, &[]).await?;
+                }
+
+                let len = buf.len().min(wanted);
+                let n = match self.client.read(&mut buf[..len]).await {
+                    Ok(0) | Err(_) => return Err(ResponderError::NetworkError),
+                    Ok(n) => n,
+                };
+                if self.chunked.is_none() {
+                    self.body_remaining -= n;
+                    self.record_body();
+                }
+                n
+            };
+
+            let Some(decoder) = &mut self.chunked else {
+                return Ok(n);
+            };
+
+            // strip the chunk framing, moving the chunk data to the start of buf
+            let mut read = 0;
+            let mut written = 0;
+            while read < n && !decoder.is_done() {
+                match decoder
+                    .advance(&buf[read..n])
+                    .map_err(ResponderError::ProtocolError)?
+                {
+                    Chunk::Framing(m) => read += m,
+                    Chunk::Data(m) => {
+                        buf.copy_within(read..read + m, written);
+                        read += m;
+                        written += m;
+                    }
+                }
+            }
+
+            let done = decoder.is_done();
+            if done {
+                self.body_remaining = 0;
+                self.record_body();
+            }
+
+            // a read holding only framing is not the end of the body
+            if written > 0 || done {
+                return Ok(written);
+            }
+        }
+    }
+
     #[must_use = "http responder not finished with either `with_body` or `no_body` results in a client waiting for data"]
     async fn with_status(&mut self, status: StatusCode) -> Result<(), ResponderError> {
-        status
+        self.status = status;
+
+        if let Some(state) = self.state {
+            state.status_sent.store(true, Ordering::Relaxed);
+        }
+
+        StatusLine(self.version, status)
             .write(self.client)
             .await
             .map_err(<WriteError as core::convert::Into<ResponderError>>::into)?;
@@ -90,26 +345,115 @@ impl<'a, 'client, C: Read + Write> ResponderInner<'a, 'client, C> {
                 .map_err(<WriteError as core::convert::Into<ResponderError>>::into)?;
         }
 
+        let connection = match (self.version, self.keep_alive) {
+            _ if status.code() == 101 => None,
+            (_, false) => Some(ResponseHeader::Connection("close")),
+            // HTTP/1.0 connections only persist when both parties explicitly agree
+            (Version::Http10, true) => Some(ResponseHeader::Connection("keep-alive")),
+            (_, true) => None,
+        };
+
+        if let Some(connection) = connection {
+            connection
+                .write(self.client)
+                .await
+                .map_err(<WriteError as core::convert::Into<ResponderError>>::into)?;
+        }
+
+        if let Some(methods) = self.allow {
+            ResponseHeader::Allow(methods).write(self.client).await?;
+        }
+
+        let default_headers = self.default_headers;
+        for header in default_headers.iter().flat_map(|headers| headers.iter()) {
+            self.with_header(*header).await?;
+        }
+
         Ok(())
     }
 
+    /// Send an interim (1xx) response with the provided Link headers ahead of the final status.
+    /// Nothing is sent to HTTP/1.0 clients which do not understand interim responses.
+    async fn informational(
+        &mut self,
+        status: StatusCode,
+        links: &[&str],
+    ) -> Result<(), ResponderError> {
+        if self.version == Version::Http10 {
+            return Ok(());
+        }
+
+        StatusLine(self.version, status)
+            .write(self.client)
+            .await
+            .map_err(<WriteError as core::convert::Into<ResponderError>>::into)?;
+
+        for link in links {
+            ResponseHeader::Link(link).write(self.client).await?;
+        }
+
+        self.client
+            .write_all(&[CR, LF])
+            .await
+            .or(Err(ResponderError::NetworkError))
+    }
+
     #[must_use = "http responder not finished with either `with_body` or `no_body` results in a client waiting for data"]
     async fn with_header(&mut self, header: ResponseHeader<'a>) -> Result<(), ResponderError> {
+        if let ResponseHeader::ContentLength(_) | ResponseHeader::TransferEncoding(_) = header {
+            self.framed = true;
+        }
+        if let ResponseHeader::Cookie(cookie) = header
+            && !cookie.is_valid()
+        {
+            return Err(ResponderError::ProtocolError("invalid cookie"));
+        }
+
         header.write(self.client).await?;
 
         Ok(())
     }
 
     async fn no_body(self) -> Result<(), ResponderError> {
+        // without a Content-Length the client can only find the end of the response by the
+        // connection closing, which never happens on a persistent connection.
+        if !self.framed && self.status.permits_body() {
+            self.client
+                .write_all(RESP_HEAD_CONTENT_LENGTH.as_bytes())
+                .await
+                .and(self.client.write_all(b": 0").await)
+                .and(self.client.write_all(&[CR, LF]).await)
+                .or(Err(ResponderError::NetworkError))?;
+        }
+
         self.client
             .write_all(&[CR, LF])
             .await
             .or(Err(ResponderError::NetworkError))?;
 
+        self.finish();
         Ok(())
     }
 
+    /// Whether the body of the response must be left out, as it is for a response to HEAD.  The
+    /// headers describing the body are still sent.
+    fn suppress_body(&self) -> bool {
+        self.method == Method::HEAD
+    }
+
+    /// Error if a Content-Length or Transfer-Encoding header has already been sent, when the body
+    /// is about to be framed by another.
+    fn check_unframed(&self) -> Result<(), ResponderError> {
+        match self.framed {
+            true => Err(ResponderError::ProtocolError(
+                "body length already sent in a header",
+            )),
+            false => Ok(()),
+        }
+    }
+
     async fn with_body(self, body: &[u8]) -> Result<(), ResponderError> {
+        self.check_unframed()?;
         ResponseHeader::ContentLength(body.len())
             .write(self.client)
             .await?;
@@ -119,20 +463,133 @@ impl<'a, 'client, C: Read + Write> ResponderInner<'a, 'client, C> {
             .await
             .or(Err(ResponderError::NetworkError))?;
 
-        if self.client.write_all(body).await.is_err() {
+        if !self.suppress_body() && self.client.write_all(body).await.is_err() {
             return Err(ResponderError::NetworkError);
         }
 
+        self.finish();
         Ok(())
     }
 
+    #[cfg(feature = "json")]
+    async fn json<T: serde::Serialize + ?Sized>(
+        mut self,
+        value: &T,
+        buf: &mut [u8],
+    ) -> Result<(), ResponderError> {
+        let len = serde_json_core::to_slice(value, buf).map_err(json_error)?;
+        self.with_header(ResponseHeader::ContentType(APPLICATION_JSON))
+            .await?;
+        self.with_body(&buf[..len]).await
+    }
+
+    #[cfg(feature = "json")]
+    async fn json_streamed<I>(mut self, items: I, buf: &mut [u8]) -> Result<(), ResponderError>
+    where
+        I: IntoIterator,
+        I::Item: serde::Serialize,
+    {
+        // HTTP/1.0 clients do not understand chunked bodies, and can only find the end of one
+        // without framing by the connection closing
+        let chunked = self.version != Version::Http10;
+        if !chunked && self.keep_alive {
+            return Err(ResponderError::ProtocolError(
+                "json cannot be streamed on a persistent HTTP/1.0 connection",
+            ));
+        }
+
+        self.check_unframed()?;
+        self.with_header(ResponseHeader::ContentType(APPLICATION_JSON))
+            .await?;
+        if chunked {
+            self.with_header(ResponseHeader::TransferEncoding("chunked"))
+                .await?;
+        }
+        self.client
+            .write_all(&[CR, LF])
+            .await
+            .or(Err(ResponderError::NetworkError))?;
+
+        if !self.suppress_body() {
+            let result = self.json_items(items, buf, chunked).await;
+            // the body has been started, so the connection is closed rather than another response
+            // sent after a failure
+            self.finish();
+            return result;
+        }
+
+        self.finish();
+        Ok(())
+    }
+
+    /// Write `items` as the elements of a JSON array, each one serialized into `buf` following the
+    /// `[` or `,` before it.
+    #[cfg(feature = "json")]
+    async fn json_items<I>(
+        &mut self,
+        items: I,
+        buf: &mut [u8],
+        chunked: bool,
+    ) -> Result<(), ResponderError>
+    where
+        I: IntoIterator,
+        I::Item: serde::Serialize,
+    {
+        let mut separator = b'[';
+        for item in items {
+            let (first, rest) = buf.split_first_mut().ok_or(ResponderError::ProtocolError(
+                "serialized json does not fit in the buffer",
+            ))?;
+            *first = separator;
+            let len = serde_json_core::to_slice(&item, rest).map_err(json_error)?;
+            self.json_chunk(&buf[..len + 1], chunked).await?;
+            separator = b',';
+        }
+
+        let end: &[u8] = match separator {
+            b'[' => b"[]",
+            _ => b"]",
+        };
+        self.json_chunk(end, chunked).await?;
+        if chunked {
+            self.client
+                .write_all(crate::chunked::LAST_CHUNK)
+                .await
+                .or(Err(ResponderError::NetworkError))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    async fn json_chunk(&mut self, data: &[u8], chunked: bool) -> Result<(), ResponderError> {
+        match chunked {
+            true => crate::chunked::write_chunk(self.client, data).await,
+            false => self.client.write_all(data).await,
+        }
+        .or(Err(ResponderError::NetworkError))
+    }
+
     async fn websocket(self) -> Result<Websocket<'client, C>, ResponderError> {
         self.client
             .write_all(&[CR, LF])
             .await
             .or(Err(ResponderError::NetworkError))?;
 
-        Ok(Websocket::new(self.client))
+        if let Some(state) = self.state {
+            state.upgraded.store(true, Ordering::Relaxed);
+        }
+        self.finish();
+        Ok(Websocket::new(self.client).routed(self.route))
+    }
+}
+
+#[cfg(feature = "json")]
+fn json_error(e: serde_json_core::ser::Error) -> ResponderError {
+    match e {
+        serde_json_core::ser::Error::BufferFull => {
+            ResponderError::ProtocolError("serialized json does not fit in the buffer")
+        }
+        _ => ResponderError::ProtocolError("value cannot be serialized as json"),
     }
 }
 
@@ -145,17 +602,215 @@ pub struct Responder<'a, 'client, C: Read + Write> {
 impl<'a, 'client, C: Read + Write> Responder<'a, 'client, C> {
     /// Create a new responder.  The initial status is set to 200 OK which will be sent to the
     /// client if the user skips straight to sending a header.  The responder sents the Server
-    /// header to the value of the Host header in the request.
+    /// header to the value of the Host header in the request.  The response uses the same HTTP
+    /// version as the request.  If the request indicated that the connection will not be kept
+    /// alive, a `Connection: close` header is sent with the status.  When responding to a HEAD
+    /// request the headers describing the body are sent, but the body itself is not, so handlers
+    /// written for GET serve HEAD correctly.
     pub fn new(request: &Request<'a>, client: &'client mut C) -> Self {
         Self {
             inner: ResponderInner {
+                state: None,
                 client,
                 status: StatusCode::OK,
                 server: ResponseHeader::Server(request.host),
+                method: request.method,
+                version: request.version,
+                keep_alive: request.keep_alive,
+                framed: false,
+                allow: None,
+                default_headers: [&[]; DEFAULT_HEADER_SETS],
+                body: request.body.unwrap_or_default(),
+                body_remaining: request.body_remaining,
+                continue_pending: request.body_remaining > 0 && request.expect.is_some(),
+                chunked: (request.body_remaining > 0
+                    && request.body_framing == Some(BodyFraming::Chunked))
+                .then(ChunkedDecoder::new),
+                route: 0,
+                route_scale: 1,
+            },
+        }
+    }
+
+    /// Create a responder that records the progress of the response in `state`.
+    pub(crate) fn new_tracked(
+        request: &Request<'a>,
+        client: &'client mut C,
+        state: &'a ResponseState,
+    ) -> Self {
+        let mut responder = Self::new(request, client);
+        responder.inner.state = Some(state);
+        responder.inner.record_body();
+        responder
+    }
+
+    /// Create a responder for a response the server generates itself when there is no valid
+    /// request to respond to.  The connection is always closed after such a response.
+    pub(crate) fn without_request(client: &'client mut C) -> Self {
+        Self {
+            inner: ResponderInner {
+                state: None,
+                client,
+                status: StatusCode::OK,
+                server: ResponseHeader::Server(""),
+                method: Method::GET,
+                version: Version::Http11,
+                keep_alive: false,
+                framed: false,
+                allow: None,
+                default_headers: [&[]; DEFAULT_HEADER_SETS],
+                body: &[],
+                body_remaining: 0,
+                continue_pending: false,
+                chunked: None,
+                route: 0,
+                route_scale: 1,
             },
         }
     }
 
+    /// Send an Allow header listing `methods` with the status.
+    pub(crate) fn allowing(mut self, methods: &'a [Method]) -> Self {
+        self.inner.allow = Some(methods);
+        self
+    }
+
+    /// Record that the request was dispatched to the `index`th of `count` alternatives, such as the
+    /// routes of a `Router` or the hosts of a `VirtualHost`.  A websocket upgraded from the
+    /// responder carries the choices recorded, so `handle_websocket` can dispatch it in the same
+    /// way with `Websocket::take_route`.  So does the responder given to `handle_error` for an
+    /// error response the server sends once the handler has returned, with
+    /// `Responder::take_route`.  Handlers nested within each other each record their choice, and
+    /// take them back in the same order.  An error is returned if `index` is not less than
+    /// `count`, or the choices recorded are too many to be held.
+    ///
+    /// This is how `Router`, `VirtualHost` and handlers declared with `routes!` find their way
+    /// back to the route taken, and is not intended to be used otherwise.
+    #[doc(hidden)]
+    pub fn with_route(mut self, index: usize, count: usize) -> Result<Self, ResponderError> {
+        const TOO_MANY: ResponderError = ResponderError::ProtocolError("too many nested routes");
+        if index >= count {
+            return Err(ResponderError::ProtocolError("route index out of range"));
+        }
+        let scale = self.inner.route_scale;
+        self.inner.route_scale = scale.checked_mul(count).ok_or(TOO_MANY)?;
+        // index < count, so this cannot exceed the new scale
+        self.inner.route += index * scale;
+        if let Some(state) = self.inner.state {
+            state.route.store(self.inner.route, Ordering::Relaxed);
+        }
+        Ok(self)
+    }
+
+    /// Take the first of the choices recorded with `Responder::with_route`, given the `count` it
+    /// was recorded with.  Returns 0 if no choice was recorded, as for an error response to a
+    /// request that could not be parsed.
+    #[doc(hidden)]
+    pub fn take_route(&mut self, count: usize) -> usize {
+        let count = count.max(1);
+        let index = self.inner.route % count;
+        self.inner.route /= count;
+        self.inner.route_scale = (self.inner.route_scale / count).max(1);
+        index
+    }
+
+    /// Carry the choices recorded while the request the response is for was dispatched.
+    pub(crate) fn routed(mut self, route: usize) -> Self {
+        self.inner.route = route;
+        self
+    }
+
+    /// Tell the client to continue sending the body of a request that expects a 100 Continue.
+    pub(crate) async fn send_continue(mut self) -> Result<(), ResponderError> {
+        self.inner.informational(StatusCode::Continue, &[]).await
+    }
+
+    /// Send a 103 Early Hints interim response with a Link header for each of the provided
+    /// values, allowing the client to start fetching resources such as stylesheets while the
+    /// final response is prepared.  The final status is sent afterwards as usual.  Nothing is
+    /// sent to HTTP/1.0 clients.  e.g. `resp.with_early_hints(&["</style.css>; rel=preload"])`
+    pub async fn with_early_hints(mut self, links: &[&str]) -> Result<Self, ResponderError> {
+        self.inner
+            .informational(StatusCode::EarlyHints, links)
+            .await?;
+
+        Ok(self)
+    }
+
+    /// Returns a reader for the request body.  The reader yields the part of the body already
+    /// received with the request first, then reads the remainder from the client until
+    /// Content-Length bytes, or the last chunk of a chunked body, have been read.  This allows
+    /// bodies larger than the server's buffer to be processed in pieces.  If the client is waiting
+    /// for a 100 Continue before sending the body, it is sent on the first read that requires it.
+    /// Any part of the body not read by the handler is discarded by the server after the response.
+    /// Each read from the client is limited to the server's body timeout.
+    ///
+    /// ```
+    /// # use embedded_io_async::{Read, Write};
+    /// # use weblite::request::Request;
+    /// # use weblite::response::{Responder, StatusCode};
+    /// # use weblite::server::HandlerError;
+    /// async fn upload<C: Read + Write>(
+    ///     req: Request<'_>,
+    ///     mut resp: Responder<'_, '_, C>,
+    /// ) -> Result<(), HandlerError> {
+    ///     let mut body = resp.body_reader();
+    ///     let mut chunk = [0u8; 512];
+    ///     loop {
+    ///         let n = body.read(&mut chunk).await?;
+    ///         if n == 0 {
+    ///             break;
+    ///         }
+    ///         // write chunk[..n] to flash
+    ///     }
+    ///
+    ///     resp.with_status(StatusCode::OK).await?.no_body().await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn body_reader(&mut self) -> BodyReader<'_, 'a, 'client, C> {
+        BodyReader {
+            inner: &mut self.inner,
+        }
+    }
+
+    /// Add headers to be sent with the status, whichever status the response ends up having.  This
+    /// allows middleware (see `weblite::layer`) to add headers to responses produced by the
+    /// handler it wraps.  Up to four sets of headers can be added, after which an error is
+    /// returned.
+    ///
+    /// ```
+    /// # use embedded_io_async::{Read, Write};
+    /// # use weblite::header::ResponseHeader;
+    /// # use weblite::response::{Responder, ResponderError};
+    /// const CORS: &[ResponseHeader] = &[
+    ///     ResponseHeader::AccessControlAllowOrigin("*"),
+    ///     ResponseHeader::Vary("Origin"),
+    /// ];
+    ///
+    /// fn cors<'a, 'client, C: Read + Write>(
+    ///     resp: Responder<'a, 'client, C>,
+    /// ) -> Result<Responder<'a, 'client, C>, ResponderError> {
+    ///     resp.with_default_headers(CORS)
+    /// }
+    /// ```
+    pub fn with_default_headers(
+        mut self,
+        headers: &'a [ResponseHeader<'a>],
+    ) -> Result<Self, ResponderError> {
+        let slot = self
+            .inner
+            .default_headers
+            .iter_mut()
+            .find(|slot| slot.is_empty())
+            .ok_or(ResponderError::ProtocolError(
+                "too many sets of default headers",
+            ))?;
+        *slot = headers;
+
+        Ok(self)
+    }
+
     /// Set and send the provided status to the client.  Consumes the `self` and returns a new self
     /// that is in the Sending state.
     #[must_use = "http responder not finished with either `with_body` or `no_body` results in a client waiting for data"]
@@ -176,12 +831,42 @@ impl<'a, 'client, C: Read + Write> Responder<'a, 'client, C> {
         header: ResponseHeader<'a>,
     ) -> Result<ResponderSending<'a, 'client, C>, ResponderError> {
         self.inner.with_status(self.inner.status).await?;
-
-        header.write(self.inner.client).await?;
+        self.inner.with_header(header).await?;
 
         Ok(ResponderSending { inner: self.inner })
     }
 
+    /// Completes the response with the current status and `value` serialized as JSON into `buf`,
+    /// with a `Content-Type: application/json` header.  An error is returned, and nothing is sent,
+    /// if the JSON does not fit in `buf`.  Available with the `json` feature.
+    #[cfg(feature = "json")]
+    pub async fn json<T: serde::Serialize + ?Sized>(
+        mut self,
+        value: &T,
+        buf: &mut [u8],
+    ) -> Result<(), ResponderError> {
+        // serialize before the status is sent so a failure can still be answered with an error
+        let len = serde_json_core::to_slice(value, buf).map_err(json_error)?;
+        self.inner.with_status(self.inner.status).await?;
+        self.inner
+            .with_header(ResponseHeader::ContentType(APPLICATION_JSON))
+            .await?;
+        self.inner.with_body(&buf[..len]).await
+    }
+
+    /// Completes the response with the current status and `items` as a JSON array, for arrays
+    /// too large to serialize into a buffer.  See `ResponderSending::json_streamed`.  Available
+    /// with the `json` feature.
+    #[cfg(feature = "json")]
+    pub async fn json_streamed<I>(mut self, items: I, buf: &mut [u8]) -> Result<(), ResponderError>
+    where
+        I: IntoIterator,
+        I::Item: serde::Serialize,
+    {
+        self.inner.with_status(self.inner.status).await?;
+        self.inner.json_streamed(items, buf).await
+    }
+
     /// Upgrade the client to a Websocket.  Consumees the self and returns a Websocket, or an error
     /// if the request doesn not contain, or contains an invalid Sec-Websocket-Key header value.
     pub async fn upgrade(
@@ -226,6 +911,37 @@ impl<'a, 'client, C: Read + Write> Responder<'a, 'client, C> {
     }
 }
 
+/// Reader for the body of a request, obtained from `Responder::body_reader()`.  Reads return
+/// `Ok(0)` once the whole body has been read.  Chunked bodies are decoded, so only the body data is
+/// returned.  A client disconnecting before sending the whole body, or taking longer than the
+/// server's body timeout to send any part of it, results in a `ResponderError::NetworkError`.  On
+/// a timeout the server sends a 408 Request Timeout if the handler has not yet sent a status, and
+/// closes the connection once the handler returns.
+pub struct BodyReader<'r, 'a, 'client, C: Read + Write> {
+    inner: &'r mut ResponderInner<'a, 'client, C>,
+}
+
+impl<C: Read + Write> BodyReader<'_, '_, '_, C> {
+    /// Number of bytes of the body that have not yet been read, or `None` if the client did not
+    /// indicate the length of the body (`Transfer-Encoding: chunked`).
+    pub fn remaining(&self) -> Option<usize> {
+        match self.inner.chunked {
+            Some(_) if self.inner.body_remaining > 0 => None,
+            _ => Some(self.inner.body.len() + self.inner.body_remaining),
+        }
+    }
+}
+
+impl<C: Read + Write> ErrorType for BodyReader<'_, '_, '_, C> {
+    type Error = ResponderError;
+}
+
+impl<C: Read + Write> Read for BodyReader<'_, '_, '_, C> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.inner.read_body(buf).await
+    }
+}
+
 /// ResponderSending is a responder for which a status has already been sent
 pub struct ResponderSending<'a, 'client, C: Read + Write> {
     inner: ResponderInner<'a, 'client, C>,
@@ -236,25 +952,87 @@ impl<'a, 'client, C: Read + Write> ResponderSending<'a, 'client, C> {
     /// Sends the supplied header to the client.  Consumes
     /// the self returning a Self in the Sending state.
     pub async fn with_header(
-        self,
+        mut self,
         header: ResponseHeader<'a>,
     ) -> Result<ResponderSending<'a, 'client, C>, ResponderError> {
-        header.write(self.inner.client).await?;
+        self.inner.with_header(header).await?;
 
         Ok(self)
     }
 
     /// Completes the response with no body.  Comsumes the self as it is not valid to produce any
-    /// more data to the client in response to the active request.
+    /// more data to the client in response to the active request.  A `Content-Length: 0` header
+    /// is sent unless a Content-Length or Transfer-Encoding header was already sent, or the status
+    /// does not permit a body.
     pub async fn no_body(self) -> Result<(), ResponderError> {
         self.inner.no_body().await
     }
 
     /// Completes the response with the supplied body setting the Content-Length to the length of the body.
     /// Comsumes the self as it is not valid to produce any more data to the client in response to the active request.
+    /// The body is not sent in response to a HEAD request.
+    /// An error is returned, and nothing sent, if a Content-Length or Transfer-Encoding header has
+    /// already been sent, as the client could not tell where the body ends.
     pub async fn with_body(self, body: &[u8]) -> Result<(), ResponderError> {
         self.inner.with_body(body).await
     }
+
+    /// Completes the response with `value` serialized as JSON into `buf`, with a
+    /// `Content-Type: application/json` header and the Content-Length of the JSON.  An error is
+    /// returned if the JSON does not fit in `buf`.  Available with the `json` feature.
+    ///
+    /// ```
+    /// # use embedded_io_async::{Read, Write};
+    /// # use serde::Serialize;
+    /// # use weblite::request::Request;
+    /// # use weblite::response::{Responder, StatusCode};
+    /// # use weblite::server::HandlerError;
+    /// #[derive(Serialize)]
+    /// struct Status {
+    ///     uptime: u64,
+    ///     temperature: f32,
+    /// }
+    ///
+    /// async fn status<C: Read + Write>(
+    ///     _req: Request<'_>,
+    ///     resp: Responder<'_, '_, C>,
+    /// ) -> Result<(), HandlerError> {
+    ///     let status = Status { uptime: 86400, temperature: 21.5 };
+    ///     let mut buf = [0u8; 64];
+    ///     resp.with_status(StatusCode::OK).await?.json(&status, &mut buf).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "json")]
+    pub async fn json<T: serde::Serialize + ?Sized>(
+        self,
+        value: &T,
+        buf: &mut [u8],
+    ) -> Result<(), ResponderError> {
+        self.inner.json(value, buf).await
+    }
+
+    /// Completes the response with `items` as a JSON array, without a buffer large enough to hold
+    /// all of it.  Each item is serialized once, into `buf`, and sent as a chunk of a
+    /// `Transfer-Encoding: chunked` body, so `buf` need only hold the largest item and its
+    /// separator.  Available with the `json` feature.
+    ///
+    /// Only the array is streamed: every item is still serialized whole into `buf` before it is
+    /// sent, so a single value too large for `buf`, such as one large struct, cannot be sent this
+    /// way.
+    ///
+    /// HTTP/1.0 clients do not understand chunked bodies, so they are sent the JSON unframed and
+    /// the connection is closed after it; an error is returned, and no body sent, if such a client
+    /// asked for the connection to be kept alive.  An item that does not fit in `buf` ends the
+    /// response with an error once the body has been started, and the connection is closed.
+    #[cfg(feature = "json")]
+    pub async fn json_streamed<I>(self, items: I, buf: &mut [u8]) -> Result<(), ResponderError>
+    where
+        I: IntoIterator,
+        I::Item: serde::Serialize,
+    {
+        self.inner.json_streamed(items, buf).await
+    }
 }
 
 #[cfg(test)]
@@ -264,7 +1042,9 @@ mod tests {
     use std::vec::Vec;
     use std::*;
 
-    use crate::request::Method;
+    use crate::cookie::SetCookie;
+    use crate::request::{Method, Version};
+    use crate::uri::Target;
 
     use super::*;
 
@@ -274,7 +1054,7 @@ mod tests {
 
     impl<'a> TestClient<'a> {
         fn new(inner: &'a mut Vec<u8>) -> Self {
-            Self { inner: inner }
+            Self { inner }
         }
     }
 
@@ -312,12 +1092,19 @@ mod tests {
         let request = Request::<'_> {
             method: Method::GET,
             path: "/",
+            target: Target::parse("/"),
+            version: Version::Http11,
             host: "RustServer",
             content_type: None,
             user_agent: None,
             content_length: 0,
             body: None,
             header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
         };
 
         let mut dst = Vec::<u8>::new();
@@ -328,6 +1115,7 @@ mod tests {
         let expected = "HTTP/1.1 200 OK\r
 Server: RustServer\r
 Content-Type: text/html\r
+Content-Length: 0\r
 \r
 "
         .as_bytes();
@@ -355,12 +1143,19 @@ Content-Type: text/html\r
         let request = Request::<'_> {
             method: Method::GET,
             path: "/",
+            target: Target::parse("/"),
+            version: Version::Http11,
             host: "RustServer",
             content_type: None,
             user_agent: None,
             content_length: 0,
             body: None,
             header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
         };
 
         let mut dst = Vec::<u8>::new();
@@ -418,12 +1213,19 @@ Content-Length: 114\r
         let request = Request::<'_> {
             method: Method::GET,
             path: "/",
+            target: Target::parse("/"),
+            version: Version::Http11,
             host: "RustServer",
             content_type: None,
             user_agent: None,
             content_length: 0,
             body: None,
             header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
         };
 
         let mut dst = Vec::<u8>::new();
@@ -443,6 +1245,7 @@ Content-Length: 114\r
         let expected = "HTTP/1.1 404 Not Found\r
 Server: RustServer\r
 Content-Type: text/html\r
+Content-Length: 0\r
 \r
 "
         .as_bytes();
@@ -460,12 +1263,19 @@ Content-Type: text/html\r
         let request = Request::<'_> {
             method: Method::GET,
             path: "/",
+            target: Target::parse("/"),
+            version: Version::Http11,
             host: "RustServer",
             content_type: None,
             user_agent: None,
             content_length: 0,
             body: None,
             header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
         };
 
         let mut dst = Vec::<u8>::new();
@@ -485,6 +1295,7 @@ Content-Type: text/html\r
         let expected = "HTTP/1.1 401\r
 Server: RustServer\r
 Content-Type: text/html\r
+Content-Length: 0\r
 \r
 "
         .as_bytes();
@@ -502,12 +1313,19 @@ Content-Type: text/html\r
         let request = Request::<'_> {
             method: Method::GET,
             path: "/",
+            target: Target::parse("/"),
+            version: Version::Http11,
             host: "RustServer",
             content_type: None,
             user_agent: None,
             content_length: 0,
             body: None,
             header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
         };
 
         let mut dst = Vec::<u8>::new();
@@ -527,6 +1345,7 @@ Content-Type: text/html\r
         let expected = "HTTP/1.1 200 OK\r
 Server: RustServer\r
 Content-Type: application/json\r
+Content-Length: 0\r
 \r
 "
         .as_bytes();
@@ -544,12 +1363,19 @@ Content-Type: application/json\r
         let request = Request::<'_> {
             method: Method::GET,
             path: "/",
+            target: Target::parse("/"),
+            version: Version::Http11,
             host: "FancyServer",
             content_type: None,
             user_agent: None,
             content_length: 0,
             body: None,
             header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
         };
 
         let mut dst = Vec::<u8>::new();
@@ -569,6 +1395,7 @@ Content-Type: application/json\r
         let expected = "HTTP/1.1 200 OK\r
 Server: FancyServer\r
 Content-Type: text/html\r
+Content-Length: 0\r
 \r
 "
         .as_bytes();
@@ -586,12 +1413,19 @@ Content-Type: text/html\r
         let request = Request::<'_> {
             method: Method::GET,
             path: "/",
+            target: Target::parse("/"),
+            version: Version::Http11,
             host: "RustServer",
             content_type: None,
             user_agent: None,
             content_length: 0,
             body: None,
             header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
         };
 
         let mut dst = Vec::<u8>::new();
@@ -615,6 +1449,7 @@ Content-Type: text/html\r
 Server: RustServer\r
 Content-Type: text/html\r
 Foo: Bar\r
+Content-Length: 0\r
 \r
 "
         .as_bytes();
@@ -632,12 +1467,19 @@ Foo: Bar\r
         let request = Request::<'_> {
             method: Method::GET,
             path: "/",
+            target: Target::parse("/"),
+            version: Version::Http11,
             host: "RustServer",
             content_type: None,
             user_agent: None,
             content_length: 0,
             body: None,
             header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
         };
 
         let mut dst = Vec::<u8>::new();
@@ -669,6 +1511,7 @@ Content-Type: text/html\r
 Foo-One: Bar\r
 Foo-Two: Baz\r
 Foo-Three: Bat\r
+Content-Length: 0\r
 \r
 "
         .as_bytes();
@@ -680,4 +1523,548 @@ Foo-Three: Bat\r
             str::from_utf8(&dst).unwrap()
         );
     }
+
+    #[tokio::test]
+    async fn test_http_response_mirrors_version() {
+        let request = Request::<'_> {
+            method: Method::GET,
+            path: "/",
+            target: Target::parse("/"),
+            version: Version::Http10,
+            host: "RustServer",
+            content_type: None,
+            user_agent: None,
+            content_length: 0,
+            body: None,
+            header_slice: None,
+            keep_alive: false,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
+        };
+
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        let resp = Responder::<'_, '_, TestClient>::new(&request, &mut writer);
+
+        resp.with_status(StatusCode::OK)
+            .await
+            .unwrap()
+            .with_body(b"ok")
+            .await
+            .unwrap();
+
+        let expected = "HTTP/1.0 200 OK\r
+Server: RustServer\r
+Connection: close\r
+Content-Length: 2\r
+\r
+ok"
+        .as_bytes();
+
+        assert_eq!(
+            &dst,
+            expected,
+            "oops, got:\n{}",
+            str::from_utf8(&dst).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_http_response_routes() {
+        let request = Request::<'_> {
+            method: Method::GET,
+            path: "/",
+            target: Target::parse("/"),
+            version: Version::Http11,
+            host: "RustServer",
+            content_type: None,
+            user_agent: None,
+            content_length: 0,
+            body: None,
+            header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
+        };
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+
+        // choices are taken back in the order they were recorded
+        let mut resp = Responder::<'_, '_, TestClient>::new(&request, &mut writer)
+            .with_route(1, 2)
+            .unwrap()
+            .with_route(4, 5)
+            .unwrap();
+        assert_eq!(resp.take_route(2), 1);
+        assert_eq!(resp.take_route(5), 4);
+        assert_eq!(resp.take_route(3), 0);
+
+        assert!(matches!(
+            resp.with_route(2, 2),
+            Err(ResponderError::ProtocolError("route index out of range"))
+        ));
+
+        let mut writer = TestClient::new(&mut dst);
+        let resp = Responder::<'_, '_, TestClient>::new(&request, &mut writer)
+            .with_route(1, usize::MAX)
+            .unwrap();
+        assert!(matches!(
+            resp.with_route(1, 2),
+            Err(ResponderError::ProtocolError("too many nested routes"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_http_response_framing_already_sent() {
+        let request = Request::<'_> {
+            method: Method::GET,
+            path: "/",
+            target: Target::parse("/"),
+            version: Version::Http11,
+            host: "RustServer",
+            content_type: None,
+            user_agent: None,
+            content_length: 0,
+            body: None,
+            header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
+        };
+
+        for (header, sent) in [
+            (ResponseHeader::ContentLength(5), "Content-Length: 5"),
+            (
+                ResponseHeader::TransferEncoding("chunked"),
+                "Transfer-Encoding: chunked",
+            ),
+        ] {
+            let mut dst = Vec::<u8>::new();
+            let mut writer = TestClient::new(&mut dst);
+            let result = Responder::<'_, '_, TestClient>::new(&request, &mut writer)
+                .with_status(StatusCode::OK)
+                .await
+                .unwrap()
+                .with_header(header)
+                .await
+                .unwrap()
+                .with_body(b"hello")
+                .await;
+            assert_eq!(
+                result,
+                Err(ResponderError::ProtocolError(
+                    "body length already sent in a header"
+                ))
+            );
+            // the body is not framed a second time
+            assert_eq!(
+                str::from_utf8(&dst).unwrap(),
+                format!("HTTP/1.1 200 OK\r\nServer: RustServer\r\n{sent}\r\n")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_response_http10_keep_alive() {
+        let request = Request::<'_> {
+            method: Method::GET,
+            path: "/",
+            target: Target::parse("/"),
+            version: Version::Http10,
+            host: "RustServer",
+            content_type: None,
+            user_agent: None,
+            content_length: 0,
+            body: None,
+            header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
+        };
+
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        let resp = Responder::<'_, '_, TestClient>::new(&request, &mut writer);
+
+        resp.with_status(StatusCode::NotFound)
+            .await
+            .unwrap()
+            .no_body()
+            .await
+            .unwrap();
+
+        let expected = "HTTP/1.0 404 Not Found\r
+Server: RustServer\r
+Connection: keep-alive\r
+Content-Length: 0\r
+\r
+"
+        .as_bytes();
+
+        assert_eq!(
+            &dst,
+            expected,
+            "oops, got:\n{}",
+            str::from_utf8(&dst).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_response_head() {
+        let request = Request::<'_> {
+            method: Method::HEAD,
+            path: "/",
+            target: Target::parse("/"),
+            version: Version::Http11,
+            host: "RustServer",
+            content_type: None,
+            user_agent: None,
+            content_length: 0,
+            body: None,
+            header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
+        };
+
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        let resp = Responder::<'_, '_, TestClient>::new(&request, &mut writer);
+
+        resp.with_status(StatusCode::OK)
+            .await
+            .unwrap()
+            .with_body("working".as_bytes())
+            .await
+            .unwrap();
+
+        // the Content-Length describes the body a GET would receive
+        assert_eq!(
+            str::from_utf8(&dst).unwrap(),
+            "HTTP/1.1 200 OK\r\nServer: RustServer\r\nContent-Length: 7\r\n\r\n"
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn test_http_response_json() {
+        #[derive(serde::Serialize)]
+        struct Status<'a> {
+            name: &'a str,
+            readings: &'a [u16],
+        }
+        let status = Status {
+            name: "pump",
+            readings: &[1000; 40],
+        };
+        let json = "{\"name\":\"pump\",\"readings\":[1000,1000,1000,1000,1000,1000,1000,1000,1000,\
+            1000,1000,1000,1000,1000,1000,1000,1000,1000,1000,1000,1000,1000,1000,1000,1000,1000,\
+            1000,1000,1000,1000,1000,1000,1000,1000,1000,1000,1000,1000,1000,1000]}";
+
+        let mut request = Request::<'_> {
+            method: Method::GET,
+            path: "/",
+            target: Target::parse("/"),
+            version: Version::Http11,
+            host: "RustServer",
+            content_type: None,
+            user_agent: None,
+            content_length: 0,
+            body: None,
+            header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
+        };
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nServer: RustServer\r\nContent-Type: application/json\r\n\
+            Content-Length: {}\r\n\r\n",
+            json.len()
+        );
+
+        // serialized into a buffer
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        let mut buf = [0u8; 256];
+        Responder::<'_, '_, TestClient>::new(&request, &mut writer)
+            .with_status(StatusCode::OK)
+            .await
+            .unwrap()
+            .json(&status, &mut buf)
+            .await
+            .unwrap();
+        assert_eq!(str::from_utf8(&dst).unwrap(), head.clone() + json);
+
+        // nothing is sent when the JSON does not fit
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        let result = Responder::<'_, '_, TestClient>::new(&request, &mut writer)
+            .json(&status, &mut buf[..64])
+            .await;
+        assert_eq!(
+            result,
+            Err(ResponderError::ProtocolError(
+                "serialized json does not fit in the buffer"
+            ))
+        );
+        assert!(dst.is_empty());
+
+        // streamed as a chunked array, one item at a time
+        let chunked_head = string::String::from(
+            "HTTP/1.1 200 OK\r\nServer: RustServer\r\nContent-Type: application/json\r\n\
+            Transfer-Encoding: chunked\r\n\r\n",
+        );
+        let items = [
+            Status {
+                name: "a",
+                readings: &[1, 2],
+            },
+            Status {
+                name: "b",
+                readings: &[],
+            },
+        ];
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        Responder::<'_, '_, TestClient>::new(&request, &mut writer)
+            .json_streamed(&items, &mut buf[..32])
+            .await
+            .unwrap();
+        assert_eq!(
+            str::from_utf8(&dst).unwrap(),
+            chunked_head.clone()
+                + "1e\r\n[{\"name\":\"a\",\"readings\":[1,2]}\r\n\
+                1b\r\n,{\"name\":\"b\",\"readings\":[]}\r\n\
+                1\r\n]\r\n0\r\n\r\n"
+        );
+
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        Responder::<'_, '_, TestClient>::new(&request, &mut writer)
+            .json_streamed(core::iter::empty::<u8>(), &mut buf)
+            .await
+            .unwrap();
+        assert_eq!(
+            str::from_utf8(&dst).unwrap(),
+            chunked_head.clone() + "2\r\n[]\r\n0\r\n\r\n"
+        );
+
+        // an item too large for the buffer ends the body without the last chunk
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        let result = Responder::<'_, '_, TestClient>::new(&request, &mut writer)
+            .json_streamed(&items, &mut buf[..16])
+            .await;
+        assert_eq!(
+            result,
+            Err(ResponderError::ProtocolError(
+                "serialized json does not fit in the buffer"
+            ))
+        );
+        assert_eq!(str::from_utf8(&dst).unwrap(), chunked_head);
+
+        // HTTP/1.0 clients are sent the array unframed on a connection that then closes
+        request.version = Version::Http10;
+        request.keep_alive = false;
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        Responder::<'_, '_, TestClient>::new(&request, &mut writer)
+            .json_streamed([1, 2, 3], &mut buf)
+            .await
+            .unwrap();
+        assert_eq!(
+            str::from_utf8(&dst).unwrap(),
+            "HTTP/1.0 200 OK\r\nServer: RustServer\r\nConnection: close\r\n\
+            Content-Type: application/json\r\n\r\n[1,2,3]"
+        );
+
+        request.keep_alive = true;
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        assert_eq!(
+            Responder::<'_, '_, TestClient>::new(&request, &mut writer)
+                .json_streamed([1, 2, 3], &mut buf)
+                .await,
+            Err(ResponderError::ProtocolError(
+                "json cannot be streamed on a persistent HTTP/1.0 connection"
+            ))
+        );
+        request.version = Version::Http11;
+
+        // only the headers are sent in response to HEAD
+        request.method = Method::HEAD;
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        Responder::<'_, '_, TestClient>::new(&request, &mut writer)
+            .json_streamed(&items, &mut buf)
+            .await
+            .unwrap();
+        assert_eq!(str::from_utf8(&dst).unwrap(), chunked_head);
+
+        // websocket messages are sent in a text frame
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        let mut ws = Websocket::new(&mut writer);
+        ws.send_json(&["on", "off"], &mut buf).await.unwrap();
+        assert_eq!(dst, b"\x81\x0c[\"on\",\"off\"]");
+        let mut writer = TestClient::new(&mut dst);
+        let mut ws = Websocket::new(&mut writer);
+        assert_eq!(
+            ws.send_json(&status, &mut buf[..8]).await,
+            Err(crate::websocket::WebsocketError::Unsupported(
+                "json payload exceeds buffer size"
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_response_set_cookie() {
+        let request = Request::<'_> {
+            method: Method::GET,
+            path: "/",
+            target: Target::parse("/"),
+            version: Version::Http11,
+            host: "RustServer",
+            content_type: None,
+            user_agent: None,
+            content_length: 0,
+            body: None,
+            header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
+        };
+
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        let resp = Responder::<'_, '_, TestClient>::new(&request, &mut writer);
+
+        resp.with_header(
+            SetCookie::new("session", "31d4d96e")
+                .path("/")
+                .http_only()
+                .into(),
+        )
+        .await
+        .unwrap()
+        .no_body()
+        .await
+        .unwrap();
+
+        assert_eq!(
+            str::from_utf8(&dst).unwrap(),
+            "HTTP/1.1 200 OK\r\nServer: RustServer\r\n\
+            Set-Cookie: session=31d4d96e; Path=/; HttpOnly\r\nContent-Length: 0\r\n\r\n"
+        );
+
+        // a value that would break out of the header is not sent
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        let resp = Responder::<'_, '_, TestClient>::new(&request, &mut writer);
+        let result = resp
+            .with_status(StatusCode::OK)
+            .await
+            .unwrap()
+            .with_header(SetCookie::new("session", "x\r\nLocation: /evil").into())
+            .await;
+        assert!(matches!(
+            result,
+            Err(ResponderError::ProtocolError("invalid cookie"))
+        ));
+        assert!(!str::from_utf8(&dst).unwrap().contains("evil"));
+    }
+
+    #[tokio::test]
+    async fn test_http_response_early_hints() {
+        let mut request = Request::<'_> {
+            method: Method::GET,
+            path: "/",
+            target: Target::parse("/"),
+            version: Version::Http11,
+            host: "RustServer",
+            content_type: None,
+            user_agent: None,
+            content_length: 0,
+            body: None,
+            header_slice: None,
+            keep_alive: true,
+            length: 0,
+            expect: None,
+            body_remaining: 0,
+            body_framing: None,
+        };
+
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        let resp = Responder::<'_, '_, TestClient>::new(&request, &mut writer);
+
+        resp.with_early_hints(&[
+            "</style.css>; rel=preload; as=style",
+            "</app.js>; rel=preload",
+        ])
+        .await
+        .unwrap()
+        .with_status(StatusCode::OK)
+        .await
+        .unwrap()
+        .no_body()
+        .await
+        .unwrap();
+
+        let expected = "HTTP/1.1 103 Early Hints\r
+Link: </style.css>; rel=preload; as=style\r
+Link: </app.js>; rel=preload\r
+\r
+HTTP/1.1 200 OK\r
+Server: RustServer\r
+Content-Length: 0\r
+\r
+"
+        .as_bytes();
+
+        assert_eq!(
+            &dst,
+            expected,
+            "oops, got:\n{}",
+            str::from_utf8(&dst).unwrap()
+        );
+
+        // HTTP/1.0 clients are only sent the final response
+        request.version = Version::Http10;
+        request.keep_alive = false;
+
+        let mut dst = Vec::<u8>::new();
+        let mut writer = TestClient::new(&mut dst);
+        let resp = Responder::<'_, '_, TestClient>::new(&request, &mut writer);
+
+        resp.with_early_hints(&["</style.css>; rel=preload; as=style"])
+            .await
+            .unwrap()
+            .with_status(StatusCode::OK)
+            .await
+            .unwrap()
+            .no_body()
+            .await
+            .unwrap();
+
+        assert!(
+            str::from_utf8(&dst)
+                .unwrap()
+                .starts_with("HTTP/1.0 200 OK\r\n")
+        );
+    }
 }
@@ -0,0 +1,800 @@
+//! A `Router` dispatches requests to a `RouteHandler` using a static table of routes, without
+//! allocation.  Each `Route` pairs a method and a path pattern with a target value, typically an
+//! enum naming the page or API endpoint, that is given to the `RouteHandler` along with any
+//! parameters captured from the path.
+//!
+//! Patterns are matched segment by segment against the path, ignoring any query string.  A
+//! segment starting with `:` captures one non-empty segment of the path, and a final segment
+//! starting with `*` captures the remainder of the path (which may be empty).  Other segments must
+//! match exactly.  Routes are tried in the order of the table, and the first match is used.  A
+//! route for GET also matches HEAD requests.
+//!
+//! When no route matches the path, `RouteHandler::handle_error` is called with a 404 Not Found.
+//! When routes match the path but not the method, an OPTIONS request is answered with the allowed
+//! methods, and other methods are sent a 405 Method Not Allowed through `handle_error`.
+//!
+//! ```
+//! use embedded_io_async::{Read, Write};
+//!
+//! use weblite::request::{Method, Request};
+//! use weblite::response::{Responder, StatusCode};
+//! use weblite::router::{Params, Route, RouteHandler, Router};
+//! use weblite::server::{HandlerError, Server};
+//! use weblite::websocket::Websocket;
+//!
+//! enum Page {
+//!     Index,
+//!     Led,
+//!     Static,
+//! }
+//!
+//! static ROUTES: [Route<Page>; 3] = [
+//!     Route::new(Method::GET, "/", Page::Index),
+//!     Route::new(Method::POST, "/led/:id", Page::Led),
+//!     Route::new(Method::GET, "/static/*file", Page::Static),
+//! ];
+//!
+//! struct Pages {}
+//!
+//! impl RouteHandler<Page> for Pages {
+//!     async fn handle_route<'client, 'buff, C: Read + Write + 'client>(
+//!         &self,
+//!         page: &Page,
+//!         params: Params<'buff>,
+//!         req: Request<'buff>,
+//!         resp: Responder<'buff, 'client, C>,
+//!     ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+//!         let body: &[u8] = match page {
+//!             Page::Index => b"<html>...",
+//!             Page::Led => match params.get("id") {
+//!                 Some("1") => b"led 1 toggled",
+//!                 _ => b"no such led",
+//!             },
+//!             Page::Static => b"...",
+//!         };
+//!
+//!         resp.with_status(StatusCode::OK).await?.with_body(body).await?;
+//!         Ok(None)
+//!     }
+//! }
+//!
+//! let server = Server::new(Router::new(&ROUTES, Pages {}));
+//! ```
+
+use embedded_io_async::{Read, Write};
+
+use crate::request::{Method, Request};
+use crate::response::{Responder, StatusCode};
+use crate::server::{HandlerError, METHODS, RequestHandler, allow_list, answer_options};
+use crate::websocket::Websocket;
+
+/// Maximum number of parameters that can be captured from a path.  Routes with more parameters
+/// never match.
+pub const MAX_PARAMS: usize = 8;
+
+/// Parameters captured from the path of a request by the `:name` and `*name` segments of a route
+/// pattern.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Params<'a> {
+    names: [&'static str; MAX_PARAMS],
+    values: [&'a str; MAX_PARAMS],
+    len: usize,
+}
+
+impl<'a> Params<'a> {
+    /// The value captured by the segment named `name`, e.g. `get("id")` for `/led/:id`.
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.iter().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
+
+    /// Iterate over the `(name, value)` pairs in the order they appear in the pattern.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &'a str)> + '_ {
+        self.names[..self.len]
+            .iter()
+            .copied()
+            .zip(self.values[..self.len].iter().copied())
+    }
+
+    /// The number of parameters captured.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no parameters were captured.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn push(&mut self, name: &'static str, value: &'a str) -> bool {
+        if self.len == MAX_PARAMS {
+            return false;
+        }
+        self.names[self.len] = name;
+        self.values[self.len] = value;
+        self.len += 1;
+        true
+    }
+}
+
+/// An entry in a `Router`'s table: requests using `method` for a path matching `pattern` are given
+/// to the `RouteHandler` with `target`.
+#[derive(Debug)]
+pub struct Route<T> {
+    method: Method,
+    pattern: &'static str,
+    target: T,
+}
+
+impl<T> Route<T> {
+    /// Create a route.  The pattern must start with a `/`.
+    pub const fn new(method: Method, pattern: &'static str, target: T) -> Self {
+        Self {
+            method,
+            pattern,
+            target,
+        }
+    }
+}
+
+/// Match `path` against `pattern`, capturing its parameters into `params`.
+fn matches<'a>(pattern: &'static str, path: &'a str, params: &mut Params<'a>) -> bool {
+    let (Some(pattern), Some(path)) = (pattern.strip_prefix('/'), path.strip_prefix('/')) else {
+        return false;
+    };
+
+    let mut rest = Some(path);
+    for segment in pattern.split('/') {
+        if let Some(name) = segment.strip_prefix('*') {
+            return params.push(name, rest.unwrap_or(""));
+        }
+
+        let Some(current) = rest else {
+            return false;
+        };
+        let (part, tail) = match current.split_once('/') {
+            Some((part, tail)) => (part, Some(tail)),
+            None => (current, None),
+        };
+
+        let matched = match segment.strip_prefix(':') {
+            Some(name) => !part.is_empty() && params.push(name, part),
+            None => segment == part,
+        };
+        if !matched {
+            return false;
+        }
+        rest = tail;
+    }
+
+    rest.is_none()
+}
+
+/// Matches a request against routes one at a time, recording the methods of the routes matching
+/// the path so the request can be answered if no route matches the method.  Used by `Router` and
+/// the `routes!` macro.
+#[doc(hidden)]
+pub struct Dispatch<'a> {
+    path: &'a str,
+    method: Method,
+    declared: [Method; METHODS.len()],
+    len: usize,
+}
+
+impl<'a> Dispatch<'a> {
+    pub fn new(req: &Request<'a>) -> Self {
+        Self {
+            path: req.target.path(),
+            method: req.method,
+            declared: METHODS,
+            len: 0,
+        }
+    }
+
+    /// The parameters captured from the path if the request matches the route.
+    pub fn route(&mut self, method: Method, pattern: &'static str) -> Option<Params<'a>> {
+        let mut params = Params::default();
+        if !matches(pattern, self.path, &mut params) {
+            return None;
+        }
+
+        if method == self.method || (self.method == Method::HEAD && method == Method::GET) {
+            return Some(params);
+        }
+
+        if !self.declared[..self.len].contains(&method) {
+            self.declared[self.len] = method;
+            self.len += 1;
+        }
+        None
+    }
+
+    /// Respond to a request that matched none of the routes.
+    pub async fn unmatched<'client, C: Read + Write + 'client, H: RequestHandler>(
+        self,
+        handler: &H,
+        resp: Responder<'_, 'client, C>,
+    ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+        let mut allow_buff = METHODS;
+        match allow_list(&self.declared[..self.len], self.method, &mut allow_buff) {
+            Some(allow) if self.len > 0 && self.method == Method::OPTIONS => {
+                answer_options(resp.allowing(allow)).await
+            }
+            Some(allow) if self.len > 0 => {
+                handler
+                    .handle_error(StatusCode::MethodNotAllowed, resp.allowing(allow))
+                    .await?;
+                Ok(None)
+            }
+            _ => {
+                handler.handle_error(StatusCode::NotFound, resp).await?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Trait implemented to handle the requests dispatched by a `Router`.
+pub trait RouteHandler<T> {
+    /// Called with the target of the route matching the request and the parameters captured from
+    /// the path.  Otherwise as `RequestHandler::handle_request`.
+    fn handle_route<'client, 'buff, C: Read + Write + 'client>(
+        &self,
+        target: &T,
+        params: Params<'buff>,
+        req: Request<'buff>,
+        resp: Responder<'buff, 'client, C>,
+    ) -> impl Future<Output = Result<Option<Websocket<'client, C>>, HandlerError>>;
+
+    /// As `RequestHandler::handle_websocket`, with the target of the route whose `handle_route`
+    /// upgraded the connection.
+    fn handle_websocket<'client, C: Read + Write + 'client>(
+        &self,
+        _target: &T,
+        mut _websocket: Websocket<'client, C>,
+        _buffer: &mut [u8],
+    ) -> impl Future<Output = Result<(), HandlerError>> {
+        async { Err(HandlerError::CustomError("websocket not implemented")) }
+    }
+
+    /// As `RequestHandler::handle_error`, additionally called with a 404 Not Found when no route
+    /// matches the path.
+    fn handle_error<'client, 'buff, C: Read + Write + 'client>(
+        &self,
+        status: StatusCode,
+        resp: Responder<'buff, 'client, C>,
+    ) -> impl Future<Output = Result<(), HandlerError>> {
+        async move {
+            resp.with_status(status).await?.no_body().await?;
+            Ok(())
+        }
+    }
+}
+
+/// A `RequestHandler` dispatching requests to a `RouteHandler` according to a table of routes.
+pub struct Router<'r, T, H> {
+    routes: &'r [Route<T>],
+    handler: H,
+}
+
+impl<'r, T, H: RouteHandler<T>> Router<'r, T, H> {
+    /// Create a router dispatching requests matching `routes` to `handler`.
+    pub fn new(routes: &'r [Route<T>], handler: H) -> Self {
+        Self { routes, handler }
+    }
+}
+
+impl<'r, T, H: RouteHandler<T>> RequestHandler for Router<'r, T, H> {
+    async fn handle_request<'client, 'buff, C: Read + Write + 'client>(
+        &self,
+        req: Request<'buff>,
+        resp: Responder<'buff, 'client, C>,
+    ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+        let mut dispatch = Dispatch::new(&req);
+        for (index, route) in self.routes.iter().enumerate() {
+            if let Some(params) = dispatch.route(route.method, route.pattern) {
+                // recorded so a websocket upgraded by the route is given its target
+                let resp = resp.with_route(index, self.routes.len())?;
+                return self
+                    .handler
+                    .handle_route(&route.target, params, req, resp)
+                    .await;
+            }
+        }
+
+        dispatch.unmatched(self, resp).await
+    }
+
+    async fn handle_websocket<'client, C: Read + Write + 'client>(
+        &self,
+        mut websocket: Websocket<'client, C>,
+        buffer: &mut [u8],
+    ) -> Result<(), HandlerError> {
+        match self.routes.get(websocket.take_route(self.routes.len())) {
+            Some(route) => {
+                self.handler
+                    .handle_websocket(&route.target, websocket, buffer)
+                    .await
+            }
+            None => Err(HandlerError::CustomError("websocket not implemented")),
+        }
+    }
+
+    async fn handle_error<'client, 'buff, C: Read + Write + 'client>(
+        &self,
+        status: StatusCode,
+        resp: Responder<'buff, 'client, C>,
+    ) -> Result<(), HandlerError> {
+        self.handler.handle_error(status, resp).await
+    }
+}
+
+/// Declare a `RequestHandler` dispatching requests to async functions by method and path pattern
+/// (see the module documentation for the pattern syntax).  The macro defines a unit struct
+/// implementing `RequestHandler`, that responds as a `Router` does when no route matches.
+///
+/// Functions for the methods `GET`, `POST`, `PUT`, `PATCH`, `DELETE`, `OPTIONS` and `HEAD` are
+/// given the request, the parameters captured from the path and the responder.  A `WS` route
+/// matches a GET request, upgrades the connection to a websocket, and calls its function with the
+/// websocket and the server's buffer.  A HEAD request for a `WS` route cannot be upgraded, and is
+/// sent a 400 Bad Request.
+///
+/// ```
+/// use embedded_io_async::{Read, Write};
+///
+/// use weblite::request::Request;
+/// use weblite::response::{Responder, StatusCode};
+/// use weblite::router::Params;
+/// use weblite::routes;
+/// use weblite::server::{HandlerError, Server};
+/// use weblite::websocket::Websocket;
+///
+/// async fn index<C: Read + Write>(
+///     _req: Request<'_>,
+///     _params: Params<'_>,
+///     resp: Responder<'_, '_, C>,
+/// ) -> Result<(), HandlerError> {
+///     resp.with_status(StatusCode::OK).await?.with_body(b"<html>...").await?;
+///     Ok(())
+/// }
+///
+/// async fn led<C: Read + Write>(
+///     _req: Request<'_>,
+///     params: Params<'_>,
+///     resp: Responder<'_, '_, C>,
+/// ) -> Result<(), HandlerError> {
+///     let _id = params.get("id");
+///     resp.with_status(StatusCode::OK).await?.no_body().await?;
+///     Ok(())
+/// }
+///
+/// async fn socket<C: Read + Write>(
+///     mut websocket: Websocket<'_, C>,
+///     buffer: &mut [u8],
+/// ) -> Result<(), HandlerError> {
+///     loop {
+///         let _msg = websocket.receive(buffer).await?;
+///     }
+/// }
+///
+/// routes! {
+///     /// The device's web interface
+///     pub struct Site {
+///         GET "/" => index,
+///         POST "/led/:id" => led,
+///         WS "/ws" => socket,
+///     }
+/// }
+///
+/// let server = Server::new(Site);
+/// ```
+#[macro_export]
+macro_rules! routes {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($method:ident $pattern:literal => $handler:path),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name;
+
+        impl $crate::server::RequestHandler for $name {
+            async fn handle_request<'client, 'buff, C>(
+                &self,
+                req: $crate::request::Request<'buff>,
+                resp: $crate::response::Responder<'buff, 'client, C>,
+            ) -> ::core::result::Result<
+                ::core::option::Option<$crate::websocket::Websocket<'client, C>>,
+                $crate::server::HandlerError,
+            >
+            where
+                C: $crate::__private::Read + $crate::__private::Write + 'client,
+            {
+                const ROUTES: usize = $crate::routes!(@count $($pattern)*);
+                let mut dispatch = $crate::router::Dispatch::new(&req);
+                let mut _index = 0usize;
+                $(
+                    $crate::routes!(@request dispatch req resp _index $method $pattern $handler);
+                    _index += 1;
+                )*
+                dispatch.unmatched(self, resp).await
+            }
+
+            async fn handle_websocket<'client, C>(
+                &self,
+                mut websocket: $crate::websocket::Websocket<'client, C>,
+                buffer: &mut [u8],
+            ) -> ::core::result::Result<(), $crate::server::HandlerError>
+            where
+                C: $crate::__private::Read + $crate::__private::Write + 'client,
+            {
+                const ROUTES: usize = $crate::routes!(@count $($pattern)*);
+                let _route = websocket.take_route(ROUTES);
+                let mut _index = 0usize;
+                $(
+                    $crate::routes!(@websocket _route _index websocket buffer $method $handler);
+                    _index += 1;
+                )*
+                Err($crate::server::HandlerError::CustomError("websocket not implemented"))
+            }
+        }
+    };
+
+    (@count $($pattern:literal)*) => {
+        <[&str]>::len(&[$($pattern),*])
+    };
+
+    (@request $dispatch:ident $req:ident $resp:ident $index:ident WS $pattern:literal $handler:path) => {
+        if $dispatch
+            .route($crate::request::Method::GET, $pattern)
+            .is_some()
+        {
+            // a HEAD request cannot be upgraded
+            if $req.method != $crate::request::Method::GET {
+                $resp
+                    .with_status($crate::response::StatusCode::BadRequest)
+                    .await?
+                    .no_body()
+                    .await?;
+                return Ok(None);
+            }
+            return Ok(Some($resp.with_route($index, ROUTES)?.upgrade($req).await?));
+        }
+    };
+
+    (@request $dispatch:ident $req:ident $resp:ident $index:ident $method:ident $pattern:literal $handler:path) => {
+        if let Some(params) = $dispatch.route($crate::request::Method::$method, $pattern) {
+            $handler($req, params, $resp).await?;
+            return Ok(None);
+        }
+    };
+
+    (@websocket $route:ident $index:ident $websocket:ident $buffer:ident WS $handler:path) => {
+        if $index == $route {
+            return $handler($websocket, $buffer).await;
+        }
+    };
+
+    (@websocket $route:ident $index:ident $websocket:ident $buffer:ident $method:ident $handler:path) => {};
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use embedded_io_async::{ErrorKind, ErrorType};
+    use std::string::String;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::server::ServerConfig;
+
+    struct TestClient<'a> {
+        inner: &'a mut Vec<u8>,
+    }
+
+    impl<'a> ErrorType for TestClient<'a> {
+        type Error = ErrorKind;
+    }
+
+    impl<'a> Write for TestClient<'a> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.inner.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    impl<'a> Read for TestClient<'a> {
+        async fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Self::Error> {
+            Ok(0)
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Page {
+        Index,
+        Led,
+        LedPost,
+        Static,
+        Events,
+        Logs,
+    }
+
+    static ROUTES: [Route<Page>; 6] = [
+        Route::new(Method::GET, "/", Page::Index),
+        Route::new(Method::GET, "/led/:id/:state", Page::Led),
+        Route::new(Method::POST, "/led/:id/:state", Page::LedPost),
+        Route::new(Method::GET, "/static/*file", Page::Static),
+        Route::new(Method::GET, "/events", Page::Events),
+        Route::new(Method::GET, "/logs", Page::Logs),
+    ];
+
+    struct Pages {}
+
+    impl RouteHandler<Page> for Pages {
+        async fn handle_route<'client, 'buff, C: Read + Write + 'client>(
+            &self,
+            page: &Page,
+            params: Params<'buff>,
+            req: Request<'buff>,
+            resp: Responder<'buff, 'client, C>,
+        ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+            if let Page::Events | Page::Logs = page {
+                return Ok(Some(resp.upgrade(req).await?));
+            }
+
+            let mut body = std::format!("{page:?}");
+            for (name, value) in params.iter() {
+                body.push_str(&std::format!(" {name}={value}"));
+            }
+
+            resp.with_status(StatusCode::OK)
+                .await?
+                .with_body(body.as_bytes())
+                .await?;
+            Ok(None)
+        }
+
+        async fn handle_websocket<'client, C: Read + Write + 'client>(
+            &self,
+            page: &Page,
+            _websocket: Websocket<'client, C>,
+            _buffer: &mut [u8],
+        ) -> Result<(), HandlerError> {
+            match page {
+                Page::Events => Err(HandlerError::CustomError("events")),
+                _ => Err(HandlerError::CustomError("logs")),
+            }
+        }
+    }
+
+    /// Upgrade `path` to a websocket with `handler`, and return the result of handling it
+    async fn upgrade<H: RequestHandler>(handler: &H, path: &str) -> Result<(), HandlerError> {
+        let mut buf = std::format!(
+            "GET {path} HTTP/1.1\r\nUpgrade: websocket\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+        )
+        .into_bytes();
+        let req = Request::parse(&mut buf, usize::MAX, &ServerConfig::default()).unwrap();
+        let mut dst = Vec::new();
+        let mut client = TestClient { inner: &mut dst };
+        let resp = Responder::new(&req, &mut client);
+        let websocket = handler.handle_request(req, resp).await?.unwrap();
+        handler.handle_websocket(websocket, &mut []).await
+    }
+
+    async fn respond<H: RequestHandler>(handler: &H, request: &str) -> String {
+        let mut buf = request.as_bytes().to_vec();
+        let req = Request::parse(&mut buf, usize::MAX, &ServerConfig::default()).unwrap();
+        let mut dst = Vec::new();
+        let mut client = TestClient { inner: &mut dst };
+        let resp = Responder::new(&req, &mut client);
+        handler.handle_request(req, resp).await.unwrap();
+        String::from_utf8(dst).unwrap()
+    }
+
+    #[test]
+    fn test_route_matching() {
+        let route = "/a/:x/b/:y";
+        let mut params = Params::default();
+        assert!(matches(route, "/a/1/b/2", &mut params));
+        assert_eq!(params.get("x"), Some("1"));
+        assert_eq!(params.get("y"), Some("2"));
+        assert_eq!(params.get("z"), None);
+        assert_eq!(params.len(), 2);
+
+        for path in ["/a/1/b", "/a/1/b/2/", "/a//b/2", "/a/1/c/2", "a/1/b/2", ""] {
+            assert!(
+                !matches(route, path, &mut Params::default()),
+                "{path} matched"
+            );
+        }
+
+        let route = "/";
+        assert!(matches(route, "/", &mut Params::default()));
+        assert!(!matches(route, "/a", &mut Params::default()));
+
+        let route = "/files/*path";
+        for (path, captured) in [
+            ("/files/a/b.txt", "a/b.txt"),
+            ("/files/", ""),
+            ("/files", ""),
+        ] {
+            let mut params = Params::default();
+            assert!(matches(route, path, &mut params), "{path} did not match");
+            assert_eq!(params.get("path"), Some(captured));
+        }
+        assert!(!matches(route, "/file", &mut Params::default()));
+
+        // too many parameters to capture
+        let route = "/:a/:b/:c/:d/:e/:f/:g/:h/:i";
+        assert!(!matches(
+            route,
+            "/1/2/3/4/5/6/7/8/9",
+            &mut Params::default()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_router_dispatch() {
+        let router = Router::new(&ROUTES, Pages {});
+
+        let ok = |body: &str| {
+            std::format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            )
+        };
+
+        assert_eq!(
+            respond(&router, "GET / HTTP/1.1\r\n\r\n").await,
+            ok("Index")
+        );
+        assert_eq!(
+            respond(&router, "GET /?a=b HTTP/1.1\r\n\r\n").await,
+            ok("Index")
+        );
+        assert_eq!(
+            respond(&router, "GET /led/2/on?x=1 HTTP/1.1\r\n\r\n").await,
+            ok("Led id=2 state=on")
+        );
+        assert_eq!(
+            respond(&router, "POST /led/2/off HTTP/1.1\r\n\r\n").await,
+            ok("LedPost id=2 state=off")
+        );
+        assert_eq!(
+            respond(&router, "GET /static/css/site.css HTTP/1.1\r\n\r\n").await,
+            ok("Static file=css/site.css")
+        );
+        assert_eq!(
+            respond(&router, "HEAD /static/a HTTP/1.1\r\n\r\n").await,
+            "HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\n"
+        );
+
+        assert_eq!(
+            respond(&router, "GET /nothing HTTP/1.1\r\n\r\n").await,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"
+        );
+        assert_eq!(
+            respond(&router, "DELETE /led/2/on HTTP/1.1\r\n\r\n").await,
+            "HTTP/1.1 405 Method Not Allowed\r\nAllow: GET, HEAD, POST, OPTIONS\r\n\
+            Content-Length: 0\r\n\r\n"
+        );
+        assert_eq!(
+            respond(&router, "OPTIONS /led/2/on HTTP/1.1\r\n\r\n").await,
+            "HTTP/1.1 204 No Content\r\nAllow: GET, HEAD, POST, OPTIONS\r\n\r\n"
+        );
+
+        // a websocket is given the target of the route that upgraded it
+        assert_eq!(
+            upgrade(&router, "/events").await,
+            Err(HandlerError::CustomError("events"))
+        );
+        assert_eq!(
+            upgrade(&router, "/logs").await,
+            Err(HandlerError::CustomError("logs"))
+        );
+    }
+
+    async fn status<C: Read + Write>(
+        _req: Request<'_>,
+        params: Params<'_>,
+        resp: Responder<'_, '_, C>,
+    ) -> Result<(), HandlerError> {
+        resp.with_status(StatusCode::OK)
+            .await?
+            .with_body(params.get("item").unwrap_or("all").as_bytes())
+            .await?;
+        Ok(())
+    }
+
+    async fn first_socket<C: Read + Write>(
+        _websocket: Websocket<'_, C>,
+        _buffer: &mut [u8],
+    ) -> Result<(), HandlerError> {
+        Err(HandlerError::CustomError("first"))
+    }
+
+    async fn second_socket<C: Read + Write>(
+        _websocket: Websocket<'_, C>,
+        _buffer: &mut [u8],
+    ) -> Result<(), HandlerError> {
+        Err(HandlerError::CustomError("second"))
+    }
+
+    crate::routes! {
+        struct Site {
+            GET "/status" => status,
+            GET "/status/:item" => status,
+            WS "/ws/first" => first_socket,
+            WS "/ws/second" => second_socket,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_routes_macro() {
+        assert_eq!(
+            respond(&Site, "GET /status HTTP/1.1\r\n\r\n").await,
+            "HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nall"
+        );
+        assert_eq!(
+            respond(&Site, "GET /status/temp HTTP/1.1\r\n\r\n").await,
+            "HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\ntemp"
+        );
+        assert_eq!(
+            respond(&Site, "GET /other HTTP/1.1\r\n\r\n").await,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"
+        );
+        assert_eq!(
+            respond(&Site, "POST /status HTTP/1.1\r\n\r\n").await,
+            "HTTP/1.1 405 Method Not Allowed\r\nAllow: GET, HEAD, OPTIONS\r\n\
+            Content-Length: 0\r\n\r\n"
+        );
+
+        assert_eq!(
+            respond(&Site, "HEAD /ws/first HTTP/1.1\r\n\r\n").await,
+            "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n"
+        );
+
+        // websockets upgraded on other connections before this one is handled do not affect it
+        let upgrade = |path: &str| {
+            std::format!(
+                "GET {path} HTTP/1.1\r\nUpgrade: websocket\r\n\
+                Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+            )
+            .into_bytes()
+        };
+        let (mut first_buf, mut second_buf) = (upgrade("/ws/first"), upgrade("/ws/second"));
+        let first_req =
+            Request::parse(&mut first_buf, usize::MAX, &ServerConfig::default()).unwrap();
+        let second_req =
+            Request::parse(&mut second_buf, usize::MAX, &ServerConfig::default()).unwrap();
+        let (mut first_dst, mut second_dst) = (Vec::new(), Vec::new());
+        let mut first_client = TestClient {
+            inner: &mut first_dst,
+        };
+        let mut second_client = TestClient {
+            inner: &mut second_dst,
+        };
+
+        let first_resp = Responder::new(&first_req, &mut first_client);
+        let first = Site.handle_request(first_req, first_resp).await;
+        let second_resp = Responder::new(&second_req, &mut second_client);
+        let second = Site.handle_request(second_req, second_resp).await;
+
+        assert_eq!(
+            Site.handle_websocket(first.unwrap().unwrap(), &mut [])
+                .await,
+            Err(HandlerError::CustomError("first"))
+        );
+        assert_eq!(
+            Site.handle_websocket(second.unwrap().unwrap(), &mut [])
+                .await,
+            Err(HandlerError::CustomError("second"))
+        );
+        assert!(
+            String::from_utf8(first_dst)
+                .unwrap()
+                .starts_with("HTTP/1.1 101 Switching Protocols\r\n")
+        );
+    }
+}
@@ -1,13 +1,98 @@
-use embedded_io_async::{Read, Write};
+use core::time::Duration;
 
-use crate::request::{Request, RequestError};
-use crate::response::{Responder, ResponderError};
-use crate::websocket::{Websocket, WebsocketError};
+use embassy_futures::select::{Either, Either3, select, select3};
+use embedded_io_async::{Error as _, ErrorKind, ErrorType, Read, Write};
+
+use crate::HttpWrite;
+use crate::ascii::{CR, LF};
+use crate::header::ResponseHeader;
+#[cfg(feature = "json")]
+use crate::json::JsonError;
+use crate::layer::{Layer, Layered};
+use crate::request::{Method, Request, RequestError};
+use crate::response::{Responder, ResponderError, ResponseState, StatusCode};
+use crate::timer::{NoTimer, Timeouts, Timer};
+use crate::websocket::{CLOSE_GOING_AWAY, Websocket, WebsocketError};
+
+/// Every method, in the order listed in an Allow header
+pub(crate) const METHODS: [Method; 7] = [
+    Method::GET,
+    Method::HEAD,
+    Method::POST,
+    Method::PUT,
+    Method::PATCH,
+    Method::DELETE,
+    Method::OPTIONS,
+];
+
+/// The methods to list in an Allow header if the server answers a request using `method` itself,
+/// because either the request is an OPTIONS request and OPTIONS is not in the `declared` methods
+/// of the resource, or `method` is not allowed.  HEAD is allowed wherever GET is.
+pub(crate) fn allow_list<'m>(
+    declared: &[Method],
+    method: Method,
+    buff: &'m mut [Method; METHODS.len()],
+) -> Option<&'m [Method]> {
+    let mut len = 0;
+    for m in METHODS {
+        let allowed = declared.contains(&m)
+            || match m {
+                Method::HEAD => declared.contains(&Method::GET),
+                Method::OPTIONS => true,
+                _ => false,
+            };
+        if allowed {
+            buff[len] = m;
+            len += 1;
+        }
+    }
+    let allow = &buff[..len];
+
+    match method {
+        Method::OPTIONS if !declared.contains(&Method::OPTIONS) => Some(allow),
+        method if allow.contains(&method) => None,
+        _ => Some(allow),
+    }
+}
+
+/// Answer an OPTIONS request for a resource with declared methods.
+pub(crate) async fn answer_options<'client, C: Read + Write>(
+    resp: Responder<'_, 'client, C>,
+) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+    resp.with_status(StatusCode::NoContent)
+        .await?
+        .no_body()
+        .await?;
+    Ok(None)
+}
+
+/// Respond to a request with `handler`, answering it without calling `handle_request` if its
+/// method is not among those declared by `RequestHandler::allowed_methods`.
+pub(crate) async fn handle_allowed<'client, C: Read + Write + 'client, H: RequestHandler>(
+    handler: &H,
+    req: Request<'_>,
+    resp: Responder<'_, 'client, C>,
+) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+    let mut allow_buff = METHODS;
+    let allow = handler
+        .allowed_methods(req.target.path())
+        .and_then(|declared| allow_list(declared, req.method, &mut allow_buff));
+    match allow {
+        None => handler.handle_request(req, resp).await,
+        Some(allow) if req.method == Method::OPTIONS => answer_options(resp.allowing(allow)).await,
+        Some(allow) => handler
+            .handle_error(StatusCode::MethodNotAllowed, resp.allowing(allow))
+            .await
+            .map(|_| None),
+    }
+}
 
 /// HandlerError is returned by `RequestHandler` implementations.  Errors returned by `Responder`
-/// method should be passed up in the `ResponderError` variant, other errors are a `CustomError`
+/// method should be passed up in the `ResponderError` variant, other errors are a `CustomError`.
+/// More variants may be added, so matches outside this crate need a wildcard arm.
 #[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
 pub enum HandlerError {
     /// Errors returned by `Responder` methods
     ResponderError(ResponderError),
@@ -15,6 +100,10 @@ pub enum HandlerError {
     WebsocketError(WebsocketError),
     /// Custom errors as specified by the `RequestHandler` implementation author
     CustomError(&'static str),
+    /// The request cannot be handled.  Unless the handler already sent a status, the server
+    /// responds with the status through `RequestHandler::handle_error`, as for a `JsonError`
+    /// converted with `?`.
+    Rejected(StatusCode),
 }
 
 impl From<ResponderError> for HandlerError {
@@ -35,6 +124,13 @@ impl From<&'static str> for HandlerError {
     }
 }
 
+#[cfg(feature = "json")]
+impl From<JsonError> for HandlerError {
+    fn from(value: JsonError) -> Self {
+        Self::Rejected(value.status())
+    }
+}
+
 /// Trait required to be implemeted be the resource that will be responsible for handling requests.
 pub trait RequestHandler {
     /// Called by the server passing a Request, and Responder.  The implementation should
@@ -58,7 +154,7 @@ pub trait RequestHandler {
     ///         req: Request<'buff>,
     ///         resp: Responder<'buff, 'client, C>
     ///     ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
-    ///         match req.path {
+    ///         match req.target.path() {
     ///             "/" => {
     ///                 resp.with_status(StatusCode::OK)
     ///                     .await?
@@ -126,6 +222,135 @@ pub trait RequestHandler {
     ) -> impl Future<Output = Result<(), HandlerError>> {
         async { Err(HandlerError::CustomError("websocket not implemented")) }
     }
+
+    /// Called by the server before `handle_request` to find the methods supported by the resource
+    /// at `path` (the path of the request target, without the query).  When the methods are
+    /// declared, the server answers an OPTIONS request with a 204 No Content listing them in an
+    /// Allow header, and a request using any other method with a 405 Method Not Allowed from
+    /// `handle_error`, without calling `handle_request`.  HEAD is allowed wherever GET is, and
+    /// OPTIONS requests are only passed to `handle_request` if OPTIONS is declared.  The default
+    /// implementation declares nothing, passing every request to `handle_request`.
+    ///
+    /// ```
+    /// use embedded_io_async::{Read, Write};
+    ///
+    /// use weblite::request::{Method, Request};
+    /// use weblite::response::{Responder, StatusCode};
+    /// use weblite::websocket::Websocket;
+    /// use weblite::server::{RequestHandler, HandlerError, Server};
+    ///
+    /// struct Handler {}
+    ///
+    /// impl RequestHandler for Handler {
+    /// #    async fn handle_request<'client, 'buff, C: Read + Write + 'client>(
+    /// #        &self,
+    /// #        req: Request<'buff>,
+    /// #        resp: Responder<'buff, 'client, C>
+    /// #    ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+    /// #        Err(HandlerError::CustomError("not implemented"))
+    /// #    }
+    ///
+    ///     fn allowed_methods(&self, path: &str) -> Option<&[Method]> {
+    ///         match path {
+    ///             "/" => Some(&[Method::GET]),
+    ///             "/settings" => Some(&[Method::GET, Method::POST]),
+    ///             _ => None,
+    ///         }
+    ///     }
+    /// }
+    fn allowed_methods(&self, _path: &str) -> Option<&[Method]> {
+        None
+    }
+
+    /// Called by the server to send an error response that was not produced by `handle_request`.
+    /// This is a 400 Bad Request for a request that cannot be parsed, a 500 Internal Server Error
+    /// when `handle_request` returns without having sent a status, the status of a
+    /// `HandlerError::Rejected` returned in the same circumstances, the status for a request that
+    /// exceeded a timeout or limit, or a 405 Method Not Allowed for a method not declared by
+    /// `allowed_methods`.  Apart from the 405, which carries an Allow header sent with the status,
+    /// the connection is closed after the response.
+    /// The default implementation sends the status with no body, implementations may override it
+    /// to send an error page instead.
+    ///
+    /// ```
+    /// use embedded_io_async::{Read, Write};
+    ///
+    /// use weblite::header::ResponseHeader;
+    /// use weblite::request::Request;
+    /// use weblite::response::{Responder, StatusCode};
+    /// use weblite::websocket::Websocket;
+    /// use weblite::server::{RequestHandler, HandlerError, Server};
+    ///
+    /// struct Handler {}
+    ///
+    /// impl RequestHandler for Handler {
+    /// #    async fn handle_request<'client, 'buff, C: Read + Write + 'client>(
+    /// #        &self,
+    /// #        req: Request<'buff>,
+    /// #        resp: Responder<'buff, 'client, C>
+    /// #    ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+    /// #        Err(HandlerError::CustomError("not implemented"))
+    /// #    }
+    ///
+    ///     async fn handle_error<'client, 'buff, C: Read + Write + 'client>(
+    ///         &self,
+    ///         status: StatusCode,
+    ///         resp: Responder<'buff, 'client, C>,
+    ///     ) -> Result<(), HandlerError> {
+    ///         let body: &[u8] = match status {
+    ///             StatusCode::InternalServerError => b"<html>device error, retry shortly</html>",
+    ///             _ => b"<html>invalid request</html>",
+    ///         };
+    ///
+    ///         resp.with_status(status)
+    ///             .await?
+    ///             .with_header(ResponseHeader::ContentType("text/html"))
+    ///             .await?
+    ///             .with_body(body)
+    ///             .await?;
+    ///
+    ///         Ok(())
+    ///     }
+    /// }
+    fn handle_error<'client, 'buff, C: Read + Write + 'client>(
+        &self,
+        status: StatusCode,
+        resp: Responder<'buff, 'client, C>,
+    ) -> impl Future<Output = Result<(), HandlerError>> {
+        async move {
+            resp.with_status(status).await?.no_body().await?;
+            Ok(())
+        }
+    }
+
+    /// Wrap the handler in a `Layer` (middleware).  Layers added later wrap those added earlier,
+    /// so in `handler.layer(a).layer(b)` requests pass through `b`, then `a`, then `handler`.
+    fn layer<L: Layer>(self, layer: L) -> Layered<Self, L>
+    where
+        Self: Sized,
+    {
+        Layered::new(self, layer)
+    }
+}
+
+/// Trait implemented by signals used to request a graceful shutdown of the server.  It is
+/// implemented for any `Fn() -> impl Future<Output = ()>`, such as a closure waiting on an
+/// `embassy_sync` watch, or one polling a flag with a timer.
+pub trait Shutdown {
+    /// Returns a future that completes once shutdown has been requested.  The server calls this
+    /// each time it waits on a client, so once shutdown has been requested the returned future
+    /// must complete immediately.
+    fn requested(&self) -> impl Future<Output = ()>;
+}
+
+impl<F, Fut> Shutdown for F
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    fn requested(&self) -> impl Future<Output = ()> {
+        self()
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -140,13 +365,54 @@ pub enum ServerError {
     BufferExceeded(u64),
     /// Error returned by handler
     HandlerError(HandlerError),
+    /// The client did not send a complete request within the configured timeouts
+    Timeout,
+    /// The request exceeded a limit of the `ServerConfig`.  The value is the status that was sent
+    /// to the client.
+    RequestTooLarge(StatusCode),
+    /// The handler returned without finishing the response.  The server completed it as far as it
+    /// could and closed the connection.
+    UnfinishedResponse,
+}
+
+/// Limits applied by the server to requests from clients.  Requests exceeding a limit are
+/// responded to with an appropriate status and the connection is closed.  Regardless of these
+/// limits, a request must also fit within the buffer provided to `Server::serve()`.  The default
+/// config applies no limits other than the size of the buffer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ServerConfig {
+    /// Maximum length of the request line (method, target and version) in bytes including the
+    /// line terminator.  Exceeding it results in a 414 URI Too Long.
+    pub max_request_line: usize,
+    /// Maximum number of headers.  Exceeding it results in a 431 Request Header Fields Too Large.
+    pub max_headers: usize,
+    /// Maximum size of the header block in bytes, including line terminators and the blank line
+    /// ending the headers.  Exceeding it results in a 431 Request Header Fields Too Large.
+    pub max_header_bytes: usize,
+    /// Maximum size of the request body in bytes as indicated by its Content-Length, or of a
+    /// chunked body decoded in the buffer.  Exceeding it results in a 413 Content Too Large.
+    pub max_body: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            max_request_line: usize::MAX,
+            max_headers: usize::MAX,
+            max_header_bytes: usize::MAX,
+            max_body: usize::MAX,
+        }
+    }
 }
 
 /// Server is the main struct to be used by users of the crate.  It is constructed with an
 /// implementation of RequestHandler, provides a serve() method to be called on each new client
 /// connection.
-pub struct Server<H> {
+pub struct Server<H, T = NoTimer> {
     handler: H,
+    timer: T,
+    timeouts: Timeouts,
+    config: ServerConfig,
 }
 
 impl<H> Server<H>
@@ -155,87 +421,427 @@ where
 {
     /// Construct an Server using the provided implementation of RequestHandler
     pub fn new(handler: H) -> Self {
-        Self { handler }
+        Self {
+            handler,
+            timer: NoTimer,
+            timeouts: Timeouts::default(),
+            config: ServerConfig::default(),
+        }
+    }
+}
+
+impl<H, T> Server<H, T>
+where
+    H: RequestHandler,
+    T: Timer,
+{
+    /// Use the provided timer to enforce the server's `Timeouts` on clients.  Without a timer
+    /// clients are never timed out.
+    pub fn with_timer<U: Timer>(self, timer: U) -> Server<H, U> {
+        Server {
+            handler: self.handler,
+            timer,
+            timeouts: self.timeouts,
+            config: self.config,
+        }
+    }
+
+    /// Replace the default `ServerConfig` limits applied to requests.
+    pub fn with_config(mut self, config: ServerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Replace the default `Timeouts` enforced on clients.
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
     }
 
     /// process requests from the client, calling the provided RequestHandler with the request and
-    /// a Responder.  The result will be `OK(())` when the client disconnects.  The result will be
-    /// an Err if there is a HTTP protocol error, or if a request is received that exceeds the
-    /// buffer size as indicated by the request Content-Length.   If a client sends more data than
-    /// is indicated by the Content-Length, then *Content-Length* bytes will be read as the body of
-    /// the current request, and then subsequent bytes will be read as the next request likely
-    /// resulting in a protocol error being returned.  Any Err(_) variant should be handled by
-    /// disconnecting the client.
+    /// a Responder.  The result will be `OK(())` when the client disconnects, or after responding
+    /// to a request that indicated the connection should not be kept alive.  The result will be
+    /// an Err if there is a HTTP protocol error, or if the request line and headers exceed the
+    /// buffer size.  A body that does not fit in the buffer with the headers is read by the
+    /// handler with `Responder::body_reader()`.  If a client sends more data than is indicated by
+    /// the Content-Length, then *Content-Length* bytes will be read as the body of the current
+    /// request, and the subsequent bytes are retained as the start of the next request on the
+    /// connection (pipelining).  If the client is too slow to send a request the client is sent a
+    /// 408 Request Timeout and the result is `Err(ServerError::Timeout)`, while a connection left
+    /// idle between requests is closed with `Ok(())`.  Requests that cannot be parsed are sent a
+    /// 400 Bad Request, and a handler returning before sending a status results in a 500 Internal
+    /// Server Error, or the status of a `HandlerError::Rejected`, all generated by
+    /// `RequestHandler::handle_error`.  A handler that sent a
+    /// status but did not finish the response has its header block terminated, and the result is
+    /// `Err(ServerError::UnfinishedResponse)`.  A client sending `Expect: 100-continue` is sent a
+    /// 100 Continue before the body is read, or a 417 Expectation Failed if the body would exceed
+    /// the limits.  Any Err(_) variant should be handled by disconnecting the client.
+    ///
+    /// Bodies sent with `Transfer-Encoding: chunked` are decoded before reaching the handler, and
+    /// a request with both a Content-Length and a Transfer-Encoding is rejected with a 400 Bad
+    /// Request.  As an unread chunked body that does not fit in the buffer cannot be skipped, the
+    /// connection is closed after the response.
     pub async fn serve<C>(&self, client: &mut C, http_buff: &mut [u8]) -> Result<(), ServerError>
     where
         C: Read + Write,
     {
+        self.serve_until(client, http_buff, &core::future::pending)
+            .await
+    }
+
+    /// As per `serve()`, but returns `Ok(())` once the provided shutdown signal completes.  No
+    /// further requests are read from the client after shutdown is requested, however a request
+    /// already being received, or being handled, is completed and responded to with a
+    /// `Connection: close` header where possible.  A websocket handler is stopped wherever it is
+    /// waiting, and the client is sent a close frame with the status 1001 (going away).
+    pub async fn serve_until<C, S>(
+        &self,
+        client: &mut C,
+        http_buff: &mut [u8],
+        shutdown: &S,
+    ) -> Result<(), ServerError>
+    where
+        C: Read + Write,
+        S: Shutdown,
+    {
+        // number of bytes at the start of http_buff holding data received from the client
+        let mut http_buff_len = 0;
+        // time by which the headers of the current request must be received
+        let mut header_deadline: Option<Duration> = None;
+        let mut shutting_down = false;
+        // whether the client has been told to continue sending the body of the current request
+        let mut continue_sent = false;
+
+        // a request line that does not fit in the buffer is too long regardless of the config
+        let limits = ServerConfig {
+            max_request_line: self.config.max_request_line.min(http_buff.len()),
+            ..self.config
+        };
+
         loop {
-            let mut http_buff_offset = 0;
-            loop {
-                let res = client.read(&mut http_buff[http_buff_offset..]).await;
-                match res {
-                    Ok(0) => {
-                        if http_buff_offset > 0 {
-                            // we filled the buffer while outstanding request data remains
-                            return Err(ServerError::BufferExceeded(0));
-                        }
-                        return Ok(());
+            let capacity = http_buff.len();
+            let data = &mut http_buff[..http_buff_len];
+            let parsed = match Request::parse(data, capacity, &limits) {
+                Err(RequestError::ExpectsContinue(n)) if continue_sent => {
+                    Err(RequestError::Incomplete(Some(n)))
+                }
+                parsed => parsed,
+            };
+            let (request_len, keep_alive) = match parsed {
+                Ok(mut request) => {
+                    request.keep_alive &= !shutting_down;
+                    let request_len = request.length;
+                    let keep_alive = request.keep_alive;
+
+                    // handle request for response
+                    let state = ResponseState::default();
+                    let mut timed = BodyTimeout {
+                        client: &mut *client,
+                        timer: &self.timer,
+                        timeout: self.timeouts.body,
+                        state: &state,
+                    };
+                    let resp = Responder::<'_, '_, _>::new_tracked(&request, &mut timed, &state);
+                    let result = handle_allowed(&self.handler, request, resp).await;
+                    if state.timed_out() {
+                        self.complete_response(
+                            client,
+                            &state,
+                            keep_alive,
+                            StatusCode::RequestTimeout,
+                        )
+                        .await;
+                        return Err(ServerError::Timeout);
                     }
-                    Ok(n) => {
-                        http_buff_offset += n;
-                        match Request::parse(&http_buff[..]) {
-                            Ok(request) => {
-                                // handle request for response
-                                let resp = Responder::<'_, '_, _>::new(&request, client);
-                                if let Err(e) =
-                                    match self.handler.handle_request(request, resp).await {
-                                        Ok(None) => break,
-                                        Ok(Some(ws)) => {
-                                            self.handler.handle_websocket(ws, http_buff).await
-                                        }
-                                        Err(e) => Err(e),
-                                    }
-                                {
-                                    match e {
-                                        HandlerError::ResponderError(
-                                            ResponderError::NetworkError,
-                                        ) => return Ok(()),
-                                        HandlerError::ResponderError(
-                                            ResponderError::ProtocolError(s),
-                                        ) => return Err(ServerError::ProtocolError(s)),
-                                        _ => return Err(ServerError::HandlerError(e)),
-                                    }
-                                }
+                    match result {
+                        Ok(None) if state.finished() => {
+                            // a body the handler did not read must be received before the next
+                            // request, unless the client is waiting to be told to send it, or
+                            // the length of a chunked body is not known.
+                            let unread = state.body_unread();
+                            if unread > 0
+                                && (!keep_alive
+                                    || unread == usize::MAX
+                                    || state.continue_pending()
+                                    || !self.discard(client, http_buff, unread).await)
+                            {
+                                return Ok(());
                             }
-                            Err(RequestError::ProtocolError(e)) => {
-                                return Err(ServerError::ProtocolError(e));
+                        }
+                        Ok(None) => {
+                            self.complete_response(
+                                client,
+                                &state,
+                                keep_alive,
+                                StatusCode::InternalServerError,
+                            )
+                            .await;
+                            return Err(ServerError::UnfinishedResponse);
+                        }
+                        Ok(Some(ws)) => {
+                            // the connection now belongs to the websocket and is finished with
+                            // when the handler is.
+                            let result = match select(
+                                self.handler.handle_websocket(ws, http_buff),
+                                shutdown.requested(),
+                            )
+                            .await
+                            {
+                                Either::First(result) => result,
+                                Either::Second(()) => Websocket::new(client)
+                                    .close(CLOSE_GOING_AWAY)
+                                    .await
+                                    .map_err(HandlerError::from),
+                            };
+                            return handler_result(result);
+                        }
+                        Err(e) => {
+                            // a client that went away cannot be sent anything
+                            let disconnected = matches!(
+                                e,
+                                HandlerError::ResponderError(ResponderError::NetworkError)
+                            );
+                            let status = match e {
+                                HandlerError::Rejected(status) => status,
+                                _ => StatusCode::InternalServerError,
+                            };
+                            if !disconnected {
+                                self.complete_response(client, &state, keep_alive, status)
+                                    .await;
                             }
-                            Err(RequestError::Incomplete(_)) => continue,
-                        };
+                            return handler_result(Err(e));
+                        }
+                    }
+
+                    (request_len, keep_alive)
+                }
+                Err(RequestError::ProtocolError(e)) => {
+                    self.reject(client, StatusCode::BadRequest).await;
+                    return Err(ServerError::ProtocolError(e));
+                }
+                Err(RequestError::UnsupportedVersion) => {
+                    self.reject(client, StatusCode::HttpVersionNotSupported)
+                        .await;
+                    return Err(ServerError::ProtocolError("unsupported http version"));
+                }
+                Err(RequestError::ExpectationFailed) => {
+                    self.reject(client, StatusCode::ExpectationFailed).await;
+                    return Err(ServerError::ProtocolError("expectation failed"));
+                }
+                Err(RequestError::ExpectsContinue(_)) => {
+                    if Responder::without_request(client)
+                        .send_continue()
+                        .await
+                        .is_err()
+                    {
+                        return Ok(());
+                    }
+                    continue_sent = true;
+                    continue;
+                }
+                Err(RequestError::UriTooLong) => {
+                    return self.too_large(client, StatusCode::UriTooLong).await;
+                }
+                Err(RequestError::HeadersTooLarge) => {
+                    return self
+                        .too_large(client, StatusCode::RequestHeaderFieldsTooLarge)
+                        .await;
+                }
+                Err(RequestError::BodyTooLarge) => {
+                    return self.too_large(client, StatusCode::ContentTooLarge).await;
+                }
+                Err(RequestError::Incomplete(content_length)) => {
+                    if http_buff_len == http_buff.len() {
+                        // bodies that do not fit are streamed to the handler, so only the
+                        // headers can fill the buffer
+                        return self
+                            .too_large(client, StatusCode::RequestHeaderFieldsTooLarge)
+                            .await;
+                    }
+
+                    let timeout = match (http_buff_len, content_length) {
+                        (0, _) => self.timeouts.idle,
+                        (_, None) => {
+                            let now = self.timer.now();
+                            header_deadline
+                                .get_or_insert(now.saturating_add(self.timeouts.header))
+                                .saturating_sub(now)
+                        }
+                        (_, Some(_)) => self.timeouts.body,
+                    };
+
+                    let shutdown_requested = async {
+                        if shutting_down {
+                            core::future::pending().await
+                        }
+                        shutdown.requested().await
+                    };
+
+                    match select3(
+                        shutdown_requested,
+                        client.read(&mut http_buff[http_buff_len..]),
+                        self.timer.delay(timeout),
+                    )
+                    .await
+                    {
+                        // nothing outstanding from the client, so simply close
+                        Either3::First(()) if http_buff_len == 0 => return Ok(()),
+                        // finish receiving the request in progress
+                        Either3::First(()) => shutting_down = true,
+                        Either3::Second(Ok(0) | Err(_)) => return Ok(()),
+                        Either3::Second(Ok(n)) => http_buff_len += n,
+                        // an idle client is closed without a response
+                        Either3::Third(()) if http_buff_len == 0 => return Ok(()),
+                        Either3::Third(()) => {
+                            self.reject(client, StatusCode::RequestTimeout).await;
+                            return Err(ServerError::Timeout);
+                        }
                     }
-                    Err(_) => return Ok(()),
-                };
+                    continue;
+                }
+            };
+
+            // retain any bytes already received for the next request
+            http_buff.copy_within(request_len..http_buff_len, 0);
+            http_buff_len -= request_len;
+            header_deadline = None;
+            continue_sent = false;
+
+            if !keep_alive {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Respond with only the status for requests the server rejects without involving the
+    /// handler.  Errors are ignored as the connection is closed after the response regardless.
+    async fn reject<C: Read + Write>(&self, client: &mut C, status: StatusCode) {
+        let resp = Responder::without_request(client);
+        let _ = self.handler.handle_error(status, resp).await;
+    }
+
+    /// Complete a response the handler did not finish so the client is not left waiting for it.
+    /// The `status` is sent if no status was sent, otherwise the header block is terminated.  The
+    /// connection must be closed afterwards as any body is missing.  Errors are ignored for the
+    /// same reason.
+    async fn complete_response<C: Read + Write>(
+        &self,
+        client: &mut C,
+        state: &ResponseState,
+        keep_alive: bool,
+        status: StatusCode,
+    ) {
+        if !state.status_sent() {
+            let resp = Responder::without_request(client).routed(state.route());
+            let _ = self.handler.handle_error(status, resp).await;
+        } else if !state.finished() {
+            if keep_alive {
+                let _ = ResponseHeader::Connection("close").write(client).await;
+            }
+            let _ = client.write_all(&[CR, LF]).await;
+        }
+    }
+
+    /// Read and discard `len` bytes from the client.  Returns false if the client disconnects, or
+    /// is too slow to send them.
+    async fn discard<C: Read>(&self, client: &mut C, buff: &mut [u8], mut len: usize) -> bool {
+        while len > 0 {
+            let n = len.min(buff.len());
+            match select(
+                client.read(&mut buff[..n]),
+                self.timer.delay(self.timeouts.body),
+            )
+            .await
+            {
+                Either::First(Ok(n)) if n > 0 => len -= n,
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// Reject a request that exceeded a limit and end the connection.
+    async fn too_large<C: Read + Write>(
+        &self,
+        client: &mut C,
+        status: StatusCode,
+    ) -> Result<(), ServerError> {
+        self.reject(client, status).await;
+        Err(ServerError::RequestTooLarge(status))
+    }
+}
+
+/// The client connection given to the handler of a request.  Reads by the handler, which are
+/// reads of the request body until the connection is upgraded to a websocket, time out after the
+/// body timeout, which is recorded in the `ResponseState` of the request.
+struct BodyTimeout<'c, C, T> {
+    client: &'c mut C,
+    timer: &'c T,
+    timeout: Duration,
+    state: &'c ResponseState,
+}
+
+impl<C: ErrorType, T> ErrorType for BodyTimeout<'_, C, T> {
+    type Error = ErrorKind;
+}
+
+impl<C: Read, T: Timer> Read for BodyTimeout<'_, C, T> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.state.upgraded() {
+            return self.client.read(buf).await.map_err(|e| e.kind());
+        }
+
+        match select(self.client.read(buf), self.timer.delay(self.timeout)).await {
+            Either::First(result) => result.map_err(|e| e.kind()),
+            Either::Second(()) => {
+                self.state.time_out();
+                Err(ErrorKind::TimedOut)
             }
         }
     }
 }
 
+impl<C: Write, T> Write for BodyTimeout<'_, C, T> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.client.write(buf).await.map_err(|e| e.kind())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.client.flush().await.map_err(|e| e.kind())
+    }
+}
+
+/// Map the result of a handler to the result of serving the client.  Network errors indicate the
+/// client went away which is not an error condition for the server.
+fn handler_result(result: Result<(), HandlerError>) -> Result<(), ServerError> {
+    match result {
+        Ok(()) => Ok(()),
+        Err(HandlerError::ResponderError(ResponderError::NetworkError)) => Ok(()),
+        Err(HandlerError::ResponderError(ResponderError::ProtocolError(s))) => {
+            Err(ServerError::ProtocolError(s))
+        }
+        Err(e) => Err(ServerError::HandlerError(e)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     extern crate std;
+    use core::cell::Cell;
+    use std::boxed::Box;
     use std::vec::Vec;
     use std::*;
 
     use embedded_io_async::{ErrorKind, ErrorType};
 
     use super::*;
-    use crate::response::StatusCode;
     use crate::websocket::Websocket;
 
     struct TestReader<'a> {
         max_reads: usize,
         reads: usize,
+        read_size: usize,
+        stall: bool,
         inner: &'a mut Vec<u8>,
     }
 
@@ -245,8 +851,21 @@ mod tests {
                 inner,
                 max_reads,
                 reads: 0,
+                read_size: usize::MAX,
+                stall: false,
             }
         }
+
+        /// Once all data is read, further reads wait forever rather than indicating EOF
+        fn with_stall(mut self) -> Self {
+            self.stall = true;
+            self
+        }
+
+        fn with_read_size(mut self, read_size: usize) -> Self {
+            self.read_size = read_size;
+            self
+        }
     }
 
     impl<'a> ErrorType for TestReader<'a> {
@@ -260,13 +879,14 @@ mod tests {
             }
             self.reads += 1;
 
-            if self.inner.len() > buf.len() {
-                buf.copy_from_slice(&self.inner[..buf.len()]);
-                return Ok(buf.len());
+            if self.inner.is_empty() && self.stall {
+                core::future::pending::<()>().await;
             }
 
-            buf[..self.inner.len()].copy_from_slice(&self.inner[..]);
-            Ok(self.inner.len())
+            let n = self.inner.len().min(buf.len()).min(self.read_size);
+            buf[..n].copy_from_slice(&self.inner[..n]);
+            self.inner.drain(..n);
+            Ok(n)
         }
     }
 
@@ -276,7 +896,7 @@ mod tests {
 
     impl<'a> TestWriter<'a> {
         fn new(inner: &'a mut Vec<u8>) -> Self {
-            Self { inner: inner }
+            Self { inner }
         }
     }
 
@@ -330,6 +950,17 @@ mod tests {
         }
     }
 
+    /// Timer for which every delay has already elapsed
+    struct ElapsedTimer;
+
+    impl Timer for ElapsedTimer {
+        fn now(&self) -> Duration {
+            Duration::ZERO
+        }
+
+        async fn delay(&self, _duration: Duration) {}
+    }
+
     struct Handler {}
     impl RequestHandler for Handler {
         async fn handle_request<'buff, 'client, C: Read + Write + 'client>(
@@ -350,6 +981,43 @@ mod tests {
                         .with_body("test1".as_bytes())
                         .await?
                 }
+                "/error" => return Err(HandlerError::CustomError("failed")),
+                "/unfinished" => {
+                    let _resp = resp
+                        .with_status(StatusCode::OK)
+                        .await?
+                        .with_header(ResponseHeader::ContentType("text/plain"))
+                        .await?;
+                }
+                "/no-response" => {}
+                "/readonly" => {
+                    resp.with_status(StatusCode::OK)
+                        .await?
+                        .with_body(req.method.as_str().as_bytes())
+                        .await?
+                }
+                "/upload" => {
+                    let mut resp = resp;
+                    let mut body = resp.body_reader();
+                    let mut chunk = [0u8; 16];
+                    let mut sum = 0u32;
+                    loop {
+                        let n = body.read(&mut chunk).await?;
+                        if n == 0 {
+                            break;
+                        }
+                        sum += chunk[..n].iter().map(|b| *b as u32).sum::<u32>();
+                    }
+
+                    resp.with_status(StatusCode::OK)
+                        .await?
+                        .with_body(std::format!("{sum}").as_bytes())
+                        .await?
+                }
+                "/error-after-status" => {
+                    let _resp = resp.with_status(StatusCode::OK).await?;
+                    return Err(HandlerError::CustomError("failed"));
+                }
                 _ => {
                     resp.with_status(StatusCode::NotFound)
                         .await?
@@ -359,6 +1027,13 @@ mod tests {
             }
             Ok(None)
         }
+
+        fn allowed_methods(&self, path: &str) -> Option<&[Method]> {
+            match path {
+                "/readonly" => Some(&[Method::GET, Method::POST]),
+                _ => None,
+            }
+        }
     }
 
     #[tokio::test]
@@ -394,4 +1069,1092 @@ working"
                 .as_bytes()
         );
     }
+
+    #[tokio::test]
+    async fn test_http_server_pipelined_requests() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        // both requests arrive in a single read
+        let mut reader_buf =
+            "GET /index.html HTTP/1.1\r\nContent-Length: 3\r\n\r\nabcGET /test1 HTTP/1.1\r\n\r\n"
+                .as_bytes()
+                .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        server.serve(&mut client, &mut http_buff[..]).await.unwrap();
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 200 OK\r
+Content-Length: 7\r
+\r
+workingHTTP/1.1 200 OK\r
+Content-Length: 5\r
+\r
+test1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_request_split_across_reads() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        let mut reader_buf = "GET /test1 HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc"
+            .as_bytes()
+            .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        // the request trickles in a few bytes at a time
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 10).with_read_size(7),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        server.serve(&mut client, &mut http_buff[..]).await.unwrap();
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 200 OK\r
+Content-Length: 5\r
+\r
+test1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_connection_close() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        // the second request must not be served as the first asked for the connection to close
+        let mut reader_buf =
+            "GET /test1 HTTP/1.1\r\nConnection: close\r\n\r\nGET /index.html HTTP/1.1\r\n\r\n"
+                .as_bytes()
+                .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        server.serve(&mut client, &mut http_buff[..]).await.unwrap();
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 200 OK\r
+Connection: close\r
+Content-Length: 5\r
+\r
+test1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_body_exceeds_buffer() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        // the unread body is discarded before the following request
+        let mut reader_buf = "GET /test1 HTTP/1.1\r\nContent-Length: 100\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        reader_buf.extend_from_slice(&[b'a'; 100]);
+        reader_buf.extend_from_slice("GET /index.html HTTP/1.1\r\n\r\n".as_bytes());
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 10),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 64];
+        assert_eq!(server.serve(&mut client, &mut http_buff[..]).await, Ok(()));
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 200 OK\r
+Content-Length: 5\r
+\r
+test1HTTP/1.1 200 OK\r
+Content-Length: 7\r
+\r
+working"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_streamed_body() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        let mut reader_buf = "POST /upload HTTP/1.1\r\nContent-Length: 1000\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        reader_buf.extend((0..1000).map(|i| (i % 251) as u8));
+        reader_buf.extend_from_slice("GET /test1 HTTP/1.1\r\n\r\n".as_bytes());
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 100).with_read_size(30),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 64];
+        assert_eq!(server.serve(&mut client, &mut http_buff[..]).await, Ok(()));
+
+        let sum: u32 = (0..1000u32).map(|i| i % 251).sum();
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            std::format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{sum}\
+                HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\ntest1",
+                std::format!("{sum}").len()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_chunked_body() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        let mut reader_buf = "POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+            3;ext=1\r\n\x01\x02\x03\r\n2\r\n\x04\x05\r\n0\r\nX-Trailer: 1\r\n\r\n\
+            GET /test1 HTTP/1.1\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert_eq!(server.serve(&mut client, &mut http_buff[..]).await, Ok(()));
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n15\
+            HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\ntest1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_streamed_chunked_body() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        let mut reader_buf = "POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        for chunk in (0..1000u32).collect::<Vec<_>>().chunks(300) {
+            reader_buf.extend_from_slice(std::format!("{:x}\r\n", chunk.len()).as_bytes());
+            reader_buf.extend(chunk.iter().map(|i| (i % 251) as u8));
+            reader_buf.extend_from_slice(b"\r\n");
+        }
+        reader_buf.extend_from_slice("0\r\n\r\nGET /test1 HTTP/1.1\r\n\r\n".as_bytes());
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 100).with_read_size(30),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 64];
+        assert_eq!(server.serve(&mut client, &mut http_buff[..]).await, Ok(()));
+
+        let sum: u32 = (0..1000u32).map(|i| i % 251).sum();
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            std::format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{sum}\
+                HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\ntest1",
+                std::format!("{sum}").len()
+            )
+        );
+
+        // a streamed chunked body the handler does not read cannot be skipped, so the connection
+        // is closed after the response
+        let mut reader_buf = "POST /test1 HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+            40\r\n0123456789012345678901234567890123456789012345678901234567890123\r\n\
+            0\r\n\r\nGET /index.html HTTP/1.1\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 100),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 64];
+        assert_eq!(server.serve(&mut client, &mut http_buff[..]).await, Ok(()));
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\ntest1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_ambiguous_length() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        let mut reader_buf = "POST /upload HTTP/1.1\r\nContent-Length: 3\r\n\
+            Transfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert!(matches!(
+            server.serve(&mut client, &mut http_buff[..]).await,
+            Err(ServerError::ProtocolError(_))
+        ));
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 400 Bad Request\r\nConnection: close\r\nContent-Length: 0\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_head() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        // the response to HEAD has no body, so the following response is not corrupted
+        let mut reader_buf = "HEAD /test1 HTTP/1.1\r\n\r\nGET /test1 HTTP/1.1\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert_eq!(server.serve(&mut client, &mut http_buff[..]).await, Ok(()));
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n\
+            HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\ntest1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_allowed_methods() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        let mut reader_buf = "OPTIONS /readonly HTTP/1.1\r\n\r\n\
+            DELETE /readonly HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc\
+            HEAD /readonly HTTP/1.1\r\n\r\n\
+            POST /readonly HTTP/1.1\r\n\r\n\
+            OPTIONS /test1 HTTP/1.1\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert_eq!(server.serve(&mut client, &mut http_buff[..]).await, Ok(()));
+
+        // paths without declared methods are left to the handler
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 204 No Content\r\nAllow: GET, HEAD, POST, OPTIONS\r\n\r\n\
+            HTTP/1.1 405 Method Not Allowed\r\nAllow: GET, HEAD, POST, OPTIONS\r\n\
+            Content-Length: 0\r\n\r\n\
+            HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\n\
+            HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nPOST\
+            HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\ntest1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_request_target() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        // the target is normalized before the handler sees it, and traversal above the root is
+        // refused
+        let mut reader_buf = "GET /static/../test1 HTTP/1.1\r\n\r\n\
+            DELETE /readonly?x=1 HTTP/1.1\r\n\r\n\
+            GET /../etc/passwd HTTP/1.1\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert!(matches!(
+            server.serve(&mut client, &mut http_buff[..]).await,
+            Err(ServerError::ProtocolError(_))
+        ));
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\ntest1\
+            HTTP/1.1 405 Method Not Allowed\r\nAllow: GET, HEAD, POST, OPTIONS\r\n\
+            Content-Length: 0\r\n\r\n\
+            HTTP/1.1 400 Bad Request\r\nConnection: close\r\nContent-Length: 0\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_http10() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        // HTTP/1.0 connections close after the response unless asked to persist
+        let mut reader_buf = "GET /test1 HTTP/1.0\r\n\r\nGET /index.html HTTP/1.0\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        server.serve(&mut client, &mut http_buff[..]).await.unwrap();
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.0 200 OK\r
+Connection: close\r
+Content-Length: 5\r
+\r
+test1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_unsupported_version() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        let mut reader_buf = "GET /test1 HTTP/2.0\r\n\r\n".as_bytes().to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert!(matches!(
+            server.serve(&mut client, &mut http_buff[..]).await,
+            Err(ServerError::ProtocolError(_))
+        ));
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 505 HTTP Version Not Supported\r
+Connection: close\r
+Content-Length: 0\r
+\r
+"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_limits() {
+        let config = ServerConfig {
+            max_request_line: 32,
+            max_headers: 4,
+            max_header_bytes: 256,
+            max_body: 16,
+        };
+
+        let cases = [
+            (
+                "GET /a/very/long/path/that/is/too/long HTTP/1.1\r\n\r\n",
+                StatusCode::UriTooLong,
+                "414 URI Too Long",
+            ),
+            (
+                "GET /test1 HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\nE: 5\r\n\r\n",
+                StatusCode::RequestHeaderFieldsTooLarge,
+                "431 Request Header Fields Too Large",
+            ),
+            (
+                "GET /test1 HTTP/1.1\r\nContent-Length: 17\r\n\r\n",
+                StatusCode::ContentTooLarge,
+                "413 Content Too Large",
+            ),
+        ];
+
+        for (request, status, status_line) in cases {
+            let server = Server::<Handler>::new(Handler {}).with_config(config);
+
+            let mut reader_buf = request.as_bytes().to_vec();
+            let mut writer_buf = Vec::<u8>::new();
+
+            let mut client = TestReaderWriter {
+                reader: TestReader::new(&mut reader_buf, 1),
+                writer: TestWriter::new(&mut writer_buf),
+            };
+
+            let mut http_buff = [0u8; 2048];
+            assert_eq!(
+                server.serve(&mut client, &mut http_buff[..]).await,
+                Err(ServerError::RequestTooLarge(status))
+            );
+
+            assert_eq!(
+                str::from_utf8(writer_buf.as_slice()).unwrap(),
+                std::format!(
+                    "HTTP/1.1 {status_line}\r\nConnection: close\r\nContent-Length: 0\r\n\r\n"
+                )
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_server_headers_exceed_buffer() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        let mut reader_buf = "GET /test1 HTTP/1.1\r\nX-Padding: ".as_bytes().to_vec();
+        reader_buf.extend_from_slice(&[b'a'; 100]);
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 10),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 64];
+        assert_eq!(
+            server.serve(&mut client, &mut http_buff[..]).await,
+            Err(ServerError::RequestTooLarge(
+                StatusCode::RequestHeaderFieldsTooLarge
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_bad_request() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        let mut reader_buf = "GET /test1 FTP\r\n\r\n".as_bytes().to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert!(matches!(
+            server.serve(&mut client, &mut http_buff[..]).await,
+            Err(ServerError::ProtocolError(_))
+        ));
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 400 Bad Request\r
+Connection: close\r
+Content-Length: 0\r
+\r
+"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_malformed_request_line() {
+        let server = Server::new(Handler {});
+
+        for request in [
+            "GET  /test1  HTTP/1.1\r\n\r\n",
+            "GET example.com:80 HTTP/1.1\r\n\r\n",
+            "GET * HTTP/1.1\r\n\r\n",
+        ] {
+            let mut reader_buf = request.as_bytes().to_vec();
+            let mut writer_buf = Vec::<u8>::new();
+
+            let mut client = TestReaderWriter {
+                reader: TestReader::new(&mut reader_buf, 1),
+                writer: TestWriter::new(&mut writer_buf),
+            };
+
+            let mut http_buff = [0u8; 2048];
+            assert!(matches!(
+                server.serve(&mut client, &mut http_buff[..]).await,
+                Err(ServerError::ProtocolError(_))
+            ));
+
+            assert_eq!(
+                str::from_utf8(writer_buf.as_slice()).unwrap(),
+                "HTTP/1.1 400 Bad Request\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+                "{request}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_server_handler_error() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        let mut reader_buf = "GET /error HTTP/1.1\r\n\r\n".as_bytes().to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert_eq!(
+            server.serve(&mut client, &mut http_buff[..]).await,
+            Err(ServerError::HandlerError(HandlerError::CustomError(
+                "failed"
+            )))
+        );
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 500 Internal Server Error\r
+Connection: close\r
+Content-Length: 0\r
+\r
+"
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn test_http_server_handler_rejected() {
+        struct JsonHandler {}
+
+        impl RequestHandler for JsonHandler {
+            async fn handle_request<'buff, 'client, C: Read + Write + 'client>(
+                &self,
+                req: Request<'buff>,
+                resp: Responder<'buff, 'client, C>,
+            ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+                let value: u32 = req.json()?;
+                resp.with_status(StatusCode::OK)
+                    .await?
+                    .with_body(std::format!("{value}").as_bytes())
+                    .await?;
+                Ok(None)
+            }
+        }
+
+        let server = Server::new(JsonHandler {});
+
+        let mut reader_buf = "POST / HTTP/1.1\r\nContent-Type: text/plain\r\n\
+            Content-Length: 1\r\n\r\n1"
+            .as_bytes()
+            .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert_eq!(
+            server.serve(&mut client, &mut http_buff[..]).await,
+            Err(ServerError::HandlerError(HandlerError::Rejected(
+                StatusCode::UnsupportedMediaType
+            )))
+        );
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 415 Unsupported Media Type\r\nConnection: close\r\n\
+            Content-Length: 0\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_handler_error_after_status() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        let mut reader_buf = "GET /error-after-status HTTP/1.1\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert!(server.serve(&mut client, &mut http_buff[..]).await.is_err());
+
+        // a second status line must not follow the one already sent
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_unfinished_response() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        // the following request is not served as the connection is closed
+        let mut reader_buf = "GET /unfinished HTTP/1.1\r\n\r\nGET /test1 HTTP/1.1\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert_eq!(
+            server.serve(&mut client, &mut http_buff[..]).await,
+            Err(ServerError::UnfinishedResponse)
+        );
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 200 OK\r
+Content-Type: text/plain\r
+Connection: close\r
+\r
+"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_no_response() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        let mut reader_buf = "GET /no-response HTTP/1.1\r\n\r\n".as_bytes().to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert_eq!(
+            server.serve(&mut client, &mut http_buff[..]).await,
+            Err(ServerError::UnfinishedResponse)
+        );
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 500 Internal Server Error\r
+Connection: close\r
+Content-Length: 0\r
+\r
+"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_error_page() {
+        struct ErrorPageHandler {}
+        impl RequestHandler for ErrorPageHandler {
+            async fn handle_request<'buff, 'client, C: Read + Write + 'client>(
+                &self,
+                _req: Request<'buff>,
+                _resp: Responder<'buff, 'client, C>,
+            ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+                Err(HandlerError::CustomError("failed"))
+            }
+
+            async fn handle_error<'buff, 'client, C: Read + Write + 'client>(
+                &self,
+                status: StatusCode,
+                resp: Responder<'buff, 'client, C>,
+            ) -> Result<(), HandlerError> {
+                resp.with_status(status)
+                    .await?
+                    .with_body("rebooting".as_bytes())
+                    .await?;
+                Ok(())
+            }
+        }
+
+        let server = Server::new(ErrorPageHandler {});
+
+        let mut reader_buf = "GET / HTTP/1.1\r\n\r\n".as_bytes().to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert!(server.serve(&mut client, &mut http_buff[..]).await.is_err());
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 500 Internal Server Error\r
+Connection: close\r
+Content-Length: 9\r
+\r
+rebooting"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_expect_continue() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        let headers = "POST /test1 HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 3\r\n\r\n";
+        let mut reader_buf = std::format!("{headers}abc").into_bytes();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 2).with_read_size(headers.len()),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert_eq!(server.serve(&mut client, &mut http_buff[..]).await, Ok(()));
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 100 Continue\r
+\r
+HTTP/1.1 200 OK\r
+Content-Length: 5\r
+\r
+test1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_expect_continue_streamed() {
+        let handler = Handler {};
+        let server = Server::<Handler>::new(handler);
+
+        // the 100 Continue is sent once the handler reads the body
+        let headers =
+            "POST /upload HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 100\r\n\r\n";
+        let mut reader_buf = headers.as_bytes().to_vec();
+        reader_buf.extend_from_slice(&[1u8; 100]);
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 10).with_read_size(headers.len()),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 96];
+        assert_eq!(server.serve(&mut client, &mut http_buff[..]).await, Ok(()));
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 100 Continue\r
+\r
+HTTP/1.1 200 OK\r
+Content-Length: 3\r
+\r
+100"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_expectation_failed() {
+        let server = Server::new(Handler {}).with_config(ServerConfig {
+            max_body: 64,
+            ..ServerConfig::default()
+        });
+
+        let mut reader_buf =
+            "POST /test1 HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 100\r\n\r\n"
+                .as_bytes()
+                .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 1),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 128];
+        assert_eq!(
+            server.serve(&mut client, &mut http_buff[..]).await,
+            Err(ServerError::ProtocolError("expectation failed"))
+        );
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 417 Expectation Failed\r
+Connection: close\r
+Content-Length: 0\r
+\r
+"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_idle_timeout() {
+        let server = Server::new(Handler {}).with_timer(ElapsedTimer);
+
+        let mut reader_buf = "GET /test1 HTTP/1.1\r\n\r\n".as_bytes().to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 10).with_stall(),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        // the request is served, then the connection is closed without a response once idle
+        let mut http_buff = [0u8; 2048];
+        server.serve(&mut client, &mut http_buff[..]).await.unwrap();
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 200 OK\r
+Content-Length: 5\r
+\r
+test1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_header_timeout() {
+        let server = Server::new(Handler {}).with_timer(ElapsedTimer);
+
+        let mut reader_buf = "GET /test1 HTTP/1.1\r\nHo".as_bytes().to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 10).with_stall(),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert_eq!(
+            server.serve(&mut client, &mut http_buff[..]).await,
+            Err(ServerError::Timeout)
+        );
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 408 Request Timeout\r
+Connection: close\r
+Content-Length: 0\r
+\r
+"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_body_timeout() {
+        let server = Server::new(Handler {}).with_timer(ElapsedTimer);
+
+        let mut reader_buf = "GET /test1 HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc"
+            .as_bytes()
+            .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 10).with_stall(),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 2048];
+        assert_eq!(
+            server.serve(&mut client, &mut http_buff[..]).await,
+            Err(ServerError::Timeout)
+        );
+
+        assert!(writer_buf.starts_with(b"HTTP/1.1 408 Request Timeout\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_http_server_streamed_body_timeout() {
+        let server = Server::new(Handler {}).with_timer(ElapsedTimer);
+
+        let mut reader_buf = "POST /upload HTTP/1.1\r\nContent-Length: 1000\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        reader_buf.extend_from_slice(&[1u8; 100]);
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 100)
+                .with_read_size(30)
+                .with_stall(),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        let mut http_buff = [0u8; 64];
+        assert_eq!(
+            server.serve(&mut client, &mut http_buff[..]).await,
+            Err(ServerError::Timeout)
+        );
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 408 Request Timeout\r\nConnection: close\r\nContent-Length: 0\r\n\r\n"
+        );
+    }
+
+    /// Returns a shutdown signal that is requested from the nth time it is waited on
+    fn shutdown_after(calls: &Cell<usize>, n: usize) -> impl Fn() -> BoxedReady + '_ {
+        move || {
+            calls.set(calls.get() + 1);
+            let requested = calls.get() >= n;
+            Box::pin(async move {
+                if !requested {
+                    core::future::pending::<()>().await
+                }
+            })
+        }
+    }
+
+    type BoxedReady = core::pin::Pin<Box<dyn Future<Output = ()>>>;
+
+    #[tokio::test]
+    async fn test_http_server_shutdown_idle() {
+        let server = Server::new(Handler {});
+
+        let mut reader_buf = "GET /test1 HTTP/1.1\r\n\r\n".as_bytes().to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 10).with_stall(),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        // no request is read once shutdown is requested
+        let mut http_buff = [0u8; 2048];
+        server
+            .serve_until(&mut client, &mut http_buff[..], &|| async {})
+            .await
+            .unwrap();
+
+        assert!(writer_buf.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_http_server_shutdown_during_request() {
+        let server = Server::new(Handler {});
+
+        let mut reader_buf = "GET /test1 HTTP/1.1\r\n\r\n".as_bytes().to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 10)
+                .with_read_size(7)
+                .with_stall(),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        // shutdown is requested after the first bytes of the request are received
+        let calls = Cell::new(0);
+        let mut http_buff = [0u8; 2048];
+        server
+            .serve_until(&mut client, &mut http_buff[..], &shutdown_after(&calls, 2))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            str::from_utf8(writer_buf.as_slice()).unwrap(),
+            "HTTP/1.1 200 OK\r
+Connection: close\r
+Content-Length: 5\r
+\r
+test1"
+        );
+    }
+
+    struct WsHandler {}
+    impl RequestHandler for WsHandler {
+        async fn handle_request<'buff, 'client, C: Read + Write + 'client>(
+            &self,
+            req: Request<'buff>,
+            resp: Responder<'buff, 'client, C>,
+        ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+            Ok(Some(resp.upgrade(req).await?))
+        }
+
+        async fn handle_websocket<'client, C: Read + Write + 'client>(
+            &self,
+            mut websocket: Websocket<'client, C>,
+            buffer: &mut [u8],
+        ) -> Result<(), HandlerError> {
+            loop {
+                websocket.receive(buffer).await?;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_server_shutdown_websocket() {
+        let server = Server::new(WsHandler {});
+
+        let mut reader_buf =
+            "GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+                .as_bytes()
+                .to_vec();
+        let mut writer_buf = Vec::<u8>::new();
+
+        let mut client = TestReaderWriter {
+            reader: TestReader::new(&mut reader_buf, 10).with_stall(),
+            writer: TestWriter::new(&mut writer_buf),
+        };
+
+        // shutdown is requested once the websocket is being handled
+        let calls = Cell::new(0);
+        let mut http_buff = [0u8; 2048];
+        server
+            .serve_until(&mut client, &mut http_buff[..], &shutdown_after(&calls, 2))
+            .await
+            .unwrap();
+
+        let upgrade = "HTTP/1.1 101 Switching Protocols\r
+Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r
+Upgrade: websocket\r
+Connection: Upgrade\r
+\r
+";
+        assert_eq!(&writer_buf[..upgrade.len()], upgrade.as_bytes());
+        // close frame with status 1001
+        assert_eq!(&writer_buf[upgrade.len()..], &[0x88, 0x02, 0x03, 0xe9]);
+    }
 }
@@ -0,0 +1,154 @@
+//! Timers allow the server to enforce timeouts on clients so that a slow, or idle client cannot
+//! hold on to a connection (and the socket behind it) indefinitely.
+//!
+//! The `Timer` trait abstracts the async runtime's clock.  Implementations are provided for
+//! `embassy-time` (feature `embassy-time`) and `tokio` (feature `tokio`).  Without a timer the
+//! server uses `NoTimer`, which never times out.
+//!
+//! ```
+//! # use embedded_io_async::{Read, Write};
+//! # use weblite::request::Request;
+//! # use weblite::response::Responder;
+//! # use weblite::websocket::Websocket;
+//! # use weblite::server::{RequestHandler, HandlerError, Server};
+//! use core::time::Duration;
+//!
+//! use weblite::timer::{Timer, Timeouts};
+//!
+//! # struct MyHandler;
+//! # impl RequestHandler for MyHandler {
+//! #     async fn handle_request<'client, 'buff, C: Read + Write + 'client>(
+//! #         &self,
+//! #         req: Request<'buff>,
+//! #         resp: Responder<'buff, 'client, C>,
+//! #     ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+//! #         Ok(None)
+//! #     }
+//! # }
+//! #
+//! /// A timer backed by the platform clock
+//! struct MyTimer;
+//!
+//! impl Timer for MyTimer {
+//!     fn now(&self) -> Duration {
+//!         // read the platform's monotonic clock
+//! #       Duration::ZERO
+//!     }
+//!
+//!     async fn delay(&self, duration: Duration) {
+//!         // sleep for the duration
+//! #       core::future::pending::<()>().await
+//!     }
+//! }
+//!
+//! let server = Server::new(MyHandler)
+//!     .with_timer(MyTimer)
+//!     .with_timeouts(Timeouts {
+//!         header: Duration::from_secs(5),
+//!         ..Timeouts::default()
+//!     });
+//! ```
+
+use core::time::Duration;
+
+/// Trait implemented for the clock of an async runtime.
+pub trait Timer {
+    /// Returns the time elapsed since some fixed point in the past.  The value must never
+    /// decrease.
+    fn now(&self) -> Duration;
+
+    /// Returns a future that completes after the provided duration has elapsed.
+    fn delay(&self, duration: Duration) -> impl Future<Output = ()>;
+}
+
+/// Timer that never completes a delay, effectively disabling all timeouts.  This is the timer
+/// used by a `Server` unless another is provided.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoTimer;
+
+impl Timer for NoTimer {
+    fn now(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    async fn delay(&self, _duration: Duration) {
+        core::future::pending().await
+    }
+}
+
+/// Timer backed by `embassy_time`.
+#[cfg(feature = "embassy-time")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EmbassyTimer;
+
+#[cfg(feature = "embassy-time")]
+impl Timer for EmbassyTimer {
+    fn now(&self) -> Duration {
+        Duration::from_micros(embassy_time::Instant::now().as_micros())
+    }
+
+    async fn delay(&self, duration: Duration) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        embassy_time::Timer::after(embassy_time::Duration::from_micros(micros)).await
+    }
+}
+
+/// Timer backed by `tokio::time`.  Must be used from within a tokio runtime.
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug)]
+pub struct TokioTimer {
+    start: tokio::time::Instant,
+}
+
+#[cfg(feature = "tokio")]
+impl TokioTimer {
+    /// Create a new TokioTimer measuring time from now.
+    pub fn new() -> Self {
+        Self {
+            start: tokio::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Default for TokioTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Timer for TokioTimer {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    async fn delay(&self, duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+}
+
+/// Timeouts enforced by the server on each client connection.  Timeouts only take effect when
+/// the server has been given a `Timer` other than `NoTimer`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timeouts {
+    /// Maximum time to receive the request line and headers of a request, measured from the
+    /// arrival of its first bytes.  On expiry the client is sent a 408 Request Timeout.
+    pub header: Duration,
+    /// Maximum time to wait for each read of the request body.  On expiry the client is sent a
+    /// 408 Request Timeout.
+    pub body: Duration,
+    /// Maximum time a connection may sit idle waiting for the first bytes of a request.  On
+    /// expiry the connection is closed without a response.
+    pub idle: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            header: Duration::from_secs(10),
+            body: Duration::from_secs(10),
+            idle: Duration::from_secs(60),
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! The target of a request (the URI in the request line), split into its authority, path and
+//! query.  The target is normalized when the request is parsed, so handlers matching or serving
+//! files by the path see a path without `.` or `..` segments.
+
+const TRAVERSAL: &str = "request target path escapes the root";
+const ENCODED_NUL: &str = "request target contains a NUL";
+
+/// The target of a request, supporting origin-form (`/index.html?a=b`), absolute-form
+/// (`http://device.local/index.html`), authority-form (`device.local:443`) and asterisk-form (`*`)
+/// targets.  Any fragment sent by the client is discarded.  A request is refused unless an
+/// asterisk-form target is for OPTIONS, and authority-form targets, which are only sent with
+/// CONNECT, are always refused.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Target<'a> {
+    authority: Option<&'a str>,
+    path: &'a str,
+    query: Option<&'a str>,
+}
+
+impl<'a> Target<'a> {
+    /// Split a target that has already been normalized.
+    pub(crate) fn parse(target: &'a str) -> Self {
+        let target = target.split_once('#').map_or(target, |(t, _)| t);
+
+        let (authority, rest) = match target.split_once("://") {
+            _ if target.starts_with('/') || target == "*" => (None, target),
+            Some((_, rest)) => {
+                let i = rest.find(['/', '?']).unwrap_or(rest.len());
+                (Some(&rest[..i]), &rest[i..])
+            }
+            None => {
+                return Self {
+                    authority: Some(target),
+                    path: "",
+                    query: None,
+                };
+            }
+        };
+
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (rest, None),
+        };
+
+        Self {
+            authority,
+            // an absolute-form target without a path refers to the root
+            path: if path.is_empty() { "/" } else { path },
+            query,
+        }
+    }
+
+    /// The path, without the query, e.g. `/index.html`.  The path is `*` for an asterisk-form
+    /// target (`OPTIONS * HTTP/1.1`), and empty for an authority-form target.
+    pub fn path(&self) -> &'a str {
+        self.path
+    }
+
+    /// The query following the `?`, if any, e.g. `a=b&c=d`.
+    pub fn query(&self) -> Option<&'a str> {
+        self.query
+    }
+
+    /// The authority (host and port) of an absolute-form or authority-form target.
+    pub fn authority(&self) -> Option<&'a str> {
+        self.authority
+    }
+}
+
+/// Whether a path segment is `.` (1) or `..` (2), including when the dots are percent-encoded.
+fn dots(segment: &[u8]) -> usize {
+    let mut rest = segment;
+    let mut n = 0;
+    while !rest.is_empty() {
+        rest = match rest {
+            [b'.', tail @ ..] => tail,
+            [b'%', b'2', b'e' | b'E', tail @ ..] => tail,
+            _ => return 0,
+        };
+        n += 1;
+    }
+    if n > 2 { 0 } else { n }
+}
+
+/// Remove the `.` and `..` segments from a path beginning with `/` in place, returning the length
+/// of the resulting path.  A `..` segment that would leave the root is an error.
+fn remove_dot_segments(path: &mut [u8]) -> Result<usize, &'static str> {
+    let len = path.len();
+    let mut out = 0;
+    let mut start = 1;
+
+    loop {
+        let end = path[start..]
+            .iter()
+            .position(|b| *b == b'/')
+            .map_or(len, |p| start + p);
+        let last = end == len;
+
+        match dots(&path[start..end]) {
+            1 => {}
+            2 => {
+                if out == 0 {
+                    return Err(TRAVERSAL);
+                }
+                out = path[..out].iter().rposition(|b| *b == b'/').unwrap_or(0);
+            }
+            _ => {
+                path[out] = b'/';
+                path.copy_within(start..end, out + 1);
+                out += 1 + end - start;
+                if last {
+                    break;
+                }
+                start = end + 1;
+                continue;
+            }
+        }
+
+        // a path ending in a dot segment refers to a directory
+        if last {
+            path[out] = b'/';
+            out += 1;
+            break;
+        }
+        start = end + 1;
+    }
+
+    Ok(out)
+}
+
+/// Normalize the request target in place, returning its new length.  Dot segments are removed from
+/// the path, the query is moved to follow the shortened path, and any fragment is dropped.
+/// Targets with a `..` segment leaving the root, or containing a NUL, are rejected.  Normalizing a
+/// normalized target leaves it unchanged.
+pub(crate) fn normalize(target: &mut [u8]) -> Result<usize, &'static str> {
+    if target.contains(&0) || target.windows(3).any(|w| w == b"%00") {
+        return Err(ENCODED_NUL);
+    }
+
+    let len = target
+        .iter()
+        .position(|b| *b == b'#')
+        .unwrap_or(target.len());
+
+    let path_start = match target[..len].windows(3).position(|w| w == b"://") {
+        _ if target.first() == Some(&b'/') => 0,
+        Some(i) => match target[i + 3..len]
+            .iter()
+            .position(|b| *b == b'/' || *b == b'?')
+        {
+            Some(p) if target[i + 3 + p] == b'/' => i + 3 + p,
+            _ => return Ok(len),
+        },
+        None => return Ok(len),
+    };
+    let path_end = target[path_start..len]
+        .iter()
+        .position(|b| *b == b'?')
+        .map_or(len, |p| path_start + p);
+
+    let path_len = remove_dot_segments(&mut target[path_start..path_end])?;
+    target.copy_within(path_end..len, path_start + path_len);
+
+    Ok(len - (path_end - path_start - path_len))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+
+    use super::*;
+
+    fn normalized(target: &str) -> Result<std::string::String, &'static str> {
+        let mut buf: Vec<u8> = target.as_bytes().to_vec();
+        let len = normalize(&mut buf)?;
+        Ok(std::string::String::from_utf8(buf[..len].to_vec()).unwrap())
+    }
+
+    #[test]
+    fn test_target_normalization() {
+        for (target, expected) in [
+            ("/", "/"),
+            ("/index.html", "/index.html"),
+            ("/a/./b", "/a/b"),
+            ("/a/b/../c", "/a/c"),
+            ("/a/b/..", "/a/"),
+            ("/a/.", "/a/"),
+            ("/a/..", "/"),
+            ("/a//b", "/a//b"),
+            ("/a/%2e%2E/b?x=/../y", "/b?x=/../y"),
+            ("/a/.%2e/b#frag", "/b"),
+            ("/a/...", "/a/..."),
+            ("/a/.b/..c", "/a/.b/..c"),
+            ("http://host/a/../b?q", "http://host/b?q"),
+            ("http://host", "http://host"),
+            ("http://host?a=/../b", "http://host?a=/../b"),
+            ("/a?u=http://b/../c", "/a?u=http://b/../c"),
+            ("host:443", "host:443"),
+            ("*", "*"),
+        ] {
+            assert_eq!(normalized(target), Ok(expected.into()), "{target}");
+            // normalizing is idempotent as requests are parsed again as more data arrives
+            assert_eq!(normalized(expected), Ok(expected.into()), "{expected}");
+        }
+
+        for target in [
+            "/..",
+            "/a/../..",
+            "/%2e%2e/etc/passwd",
+            "/a%00b",
+            "/a?b=%00",
+        ] {
+            assert!(normalized(target).is_err(), "{target}");
+        }
+    }
+
+    #[test]
+    fn test_target_parse() {
+        let target = Target::parse("/index.html?a=b&c=d");
+        assert_eq!(target.path(), "/index.html");
+        assert_eq!(target.query(), Some("a=b&c=d"));
+        assert_eq!(target.authority(), None);
+
+        let target = Target::parse("/status");
+        assert_eq!(target.path(), "/status");
+        assert_eq!(target.query(), None);
+
+        let target = Target::parse("http://device.local:8080/status?x=1");
+        assert_eq!(target.authority(), Some("device.local:8080"));
+        assert_eq!(target.path(), "/status");
+        assert_eq!(target.query(), Some("x=1"));
+
+        let target = Target::parse("http://device.local?x=1");
+        assert_eq!(target.authority(), Some("device.local"));
+        assert_eq!(target.path(), "/");
+        assert_eq!(target.query(), Some("x=1"));
+        assert_eq!(Target::parse("http://device.local").path(), "/");
+        assert_eq!(Target::parse("/a?u=http://b/c").authority(), None);
+
+        let target = Target::parse("device.local:443");
+        assert_eq!(target.authority(), Some("device.local:443"));
+        assert_eq!(target.path(), "");
+
+        assert_eq!(Target::parse("*").path(), "*");
+        assert_eq!(Target::parse("/a#b").path(), "/a");
+    }
+}
@@ -0,0 +1,393 @@
+//! Virtual hosts allow different handlers to serve requests depending on the Host header, such as
+//! an admin interface on `device.local` and a public status page on the device's IP address.
+//!
+//! The host of a request with an absolute-form target (`GET http://device.local/ HTTP/1.1`), as
+//! sent to a proxy, is the authority of the target, and its Host header is ignored.
+//!
+//! Host patterns are matched case-insensitively.  A pattern without a port matches the host on any
+//! port, while a pattern with a port (`device.local:8080`) only matches that port.  A pattern
+//! starting with `*.` matches any subdomain of the rest of the pattern, but not the domain itself.
+//!
+//! ```
+//! use embedded_io_async::{Read, Write};
+//!
+//! use weblite::request::Request;
+//! use weblite::response::Responder;
+//! use weblite::server::{HandlerError, RequestHandler, Server};
+//! use weblite::vhost::VirtualHost;
+//! use weblite::websocket::Websocket;
+//!
+//! # struct Admin {}
+//! # struct Portal {}
+//! # struct Status {}
+//! # macro_rules! handler {
+//! #     ($name:ident) => {
+//! #         impl RequestHandler for $name {
+//! #             async fn handle_request<'client, 'buff, C: Read + Write + 'client>(
+//! #                 &self,
+//! #                 req: Request<'buff>,
+//! #                 resp: Responder<'buff, 'client, C>
+//! #             ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+//! #                 Err(HandlerError::CustomError("not implemented"))
+//! #             }
+//! #         }
+//! #     };
+//! # }
+//! # handler!(Admin);
+//! # handler!(Portal);
+//! # handler!(Status);
+//! // requests for any other host are served the status page
+//! let handler = VirtualHost::new("device.local", Admin {}, Status {})
+//!     .host("*.captive.example", Portal {});
+//! let server = Server::new(handler);
+//! ```
+
+use embedded_io_async::{Read, Write};
+
+use crate::request::Request;
+use crate::response::{Responder, StatusCode};
+use crate::server::{HandlerError, RequestHandler, handle_allowed};
+use crate::websocket::Websocket;
+
+/// A `RequestHandler` giving requests for hosts matching a pattern to one handler, and all other
+/// requests to a fallback handler.
+///
+/// The methods declared by each handler's `RequestHandler::allowed_methods` apply to the requests
+/// given to it, and error responses the server sends once a handler has returned, such as a 500
+/// Internal Server Error, are sent by that handler's `handle_error`.  Error responses the server
+/// generates without a request, such as a 400 Bad Request for a request that cannot be parsed, are
+/// sent by the fallback.
+pub struct VirtualHost<H, F> {
+    pattern: &'static str,
+    handler: H,
+    fallback: F,
+}
+
+impl<H: RequestHandler, F: RequestHandler> VirtualHost<H, F> {
+    /// Serve requests for hosts matching `pattern` with `handler`, and all others with `fallback`.
+    pub fn new(pattern: &'static str, handler: H, fallback: F) -> Self {
+        Self {
+            pattern,
+            handler,
+            fallback,
+        }
+    }
+
+    /// Serve requests for hosts matching `pattern` with `handler`, ahead of the hosts already
+    /// configured.
+    pub fn host<H2: RequestHandler>(
+        self,
+        pattern: &'static str,
+        handler: H2,
+    ) -> VirtualHost<H2, Self> {
+        VirtualHost::new(pattern, handler, self)
+    }
+}
+
+// the handler a request is given to is recorded as the route 1, and the fallback as 0
+impl<H: RequestHandler, F: RequestHandler> RequestHandler for VirtualHost<H, F> {
+    async fn handle_request<'client, 'buff, C: Read + Write + 'client>(
+        &self,
+        req: Request<'buff>,
+        resp: Responder<'buff, 'client, C>,
+    ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+        match host_matches(self.pattern, request_host(&req)) {
+            true => handle_allowed(&self.handler, req, resp.with_route(1, 2)?).await,
+            false => handle_allowed(&self.fallback, req, resp.with_route(0, 2)?).await,
+        }
+    }
+
+    async fn handle_websocket<'client, C: Read + Write + 'client>(
+        &self,
+        mut websocket: Websocket<'client, C>,
+        buffer: &mut [u8],
+    ) -> Result<(), HandlerError> {
+        match websocket.take_route(2) {
+            1 => self.handler.handle_websocket(websocket, buffer).await,
+            _ => self.fallback.handle_websocket(websocket, buffer).await,
+        }
+    }
+
+    async fn handle_error<'client, 'buff, C: Read + Write + 'client>(
+        &self,
+        status: StatusCode,
+        mut resp: Responder<'buff, 'client, C>,
+    ) -> Result<(), HandlerError> {
+        match resp.take_route(2) {
+            1 => self.handler.handle_error(status, resp).await,
+            _ => self.fallback.handle_error(status, resp).await,
+        }
+    }
+}
+
+/// The host a request is for, which is the authority of an absolute-form target in place of the
+/// Host header (RFC 9112 section 3.2.2), without any user information.
+fn request_host<'a>(req: &Request<'a>) -> &'a str {
+    match req.target.authority() {
+        Some(authority) => authority.rsplit('@').next().unwrap_or(authority),
+        None => req.host,
+    }
+}
+
+/// Split the port from a host e.g. `device.local:8080` or `[::1]:8080`.  A trailing dot on the
+/// name (a fully qualified name) is removed.
+fn split_port(host: &str) -> (&str, Option<&str>) {
+    let (name, port) = match host.rsplit_once(':') {
+        // an IPv6 address without a port has colons, but is enclosed in brackets
+        Some((name, port)) if !name.contains(':') || name.ends_with(']') => (name, Some(port)),
+        _ => (host, None),
+    };
+
+    (name.strip_suffix('.').unwrap_or(name), port)
+}
+
+/// Whether the Host header value `host` matches `pattern`.
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let (pattern, pattern_port) = split_port(pattern);
+    let (host, port) = split_port(host);
+
+    if pattern_port.is_some() && pattern_port != port {
+        return false;
+    }
+
+    match pattern.strip_prefix("*.") {
+        Some(domain) => {
+            let host = host.as_bytes();
+            let domain = domain.as_bytes();
+            host.len() > domain.len() + 1
+                && host[host.len() - domain.len() - 1] == b'.'
+                && host[host.len() - domain.len()..].eq_ignore_ascii_case(domain)
+        }
+        None => pattern.eq_ignore_ascii_case(host),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use embedded_io_async::{ErrorKind, ErrorType};
+    use std::string::String;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::request::Method;
+    use crate::server::{Server, ServerConfig};
+
+    struct TestClient<'a> {
+        inner: &'a mut Vec<u8>,
+        input: &'a [u8],
+    }
+
+    impl<'a> ErrorType for TestClient<'a> {
+        type Error = ErrorKind;
+    }
+
+    impl<'a> Write for TestClient<'a> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.inner.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    impl<'a> Read for TestClient<'a> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let n = self.input.len().min(buf.len());
+            buf[..n].copy_from_slice(&self.input[..n]);
+            self.input = &self.input[n..];
+            Ok(n)
+        }
+    }
+
+    /// Responds with its name, and fails websockets with its name.  Error responses also carry
+    /// its name.
+    struct Named(&'static str);
+
+    impl RequestHandler for Named {
+        async fn handle_request<'client, 'buff, C: Read + Write + 'client>(
+            &self,
+            req: Request<'buff>,
+            resp: Responder<'buff, 'client, C>,
+        ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
+            match req.target.path() {
+                "/ws" => return Ok(Some(resp.upgrade(req).await?)),
+                "/unfinished" => return Ok(None),
+                _ => {}
+            }
+
+            resp.with_status(StatusCode::OK)
+                .await?
+                .with_body(self.0.as_bytes())
+                .await?;
+            Ok(None)
+        }
+
+        async fn handle_websocket<'client, C: Read + Write + 'client>(
+            &self,
+            _websocket: Websocket<'client, C>,
+            _buffer: &mut [u8],
+        ) -> Result<(), HandlerError> {
+            Err(HandlerError::CustomError(self.0))
+        }
+
+        fn allowed_methods(&self, path: &str) -> Option<&[Method]> {
+            match path {
+                "/readonly" => Some(&[Method::GET]),
+                _ => None,
+            }
+        }
+
+        async fn handle_error<'client, 'buff, C: Read + Write + 'client>(
+            &self,
+            status: StatusCode,
+            resp: Responder<'buff, 'client, C>,
+        ) -> Result<(), HandlerError> {
+            resp.with_status(status)
+                .await?
+                .with_body(self.0.as_bytes())
+                .await?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_host_matching() {
+        for (pattern, host) in [
+            ("device.local", "device.local"),
+            ("device.local", "Device.LOCAL"),
+            ("device.local", "device.local:8080"),
+            ("device.local", "device.local."),
+            ("device.local:8080", "device.local:8080"),
+            ("*.example.com", "a.example.com"),
+            ("*.example.com", "a.b.Example.com:80"),
+            ("192.168.4.1", "192.168.4.1:80"),
+            ("[::1]", "[::1]:80"),
+            ("[::1]", "[::1]"),
+        ] {
+            assert!(host_matches(pattern, host), "{pattern} {host}");
+        }
+
+        for (pattern, host) in [
+            ("device.local", "other.local"),
+            ("device.local", "device.local.evil"),
+            ("device.local:8080", "device.local"),
+            ("device.local:8080", "device.local:80"),
+            ("*.example.com", "example.com"),
+            ("*.example.com", "aexample.com"),
+            ("*.example.com", ".example.com"),
+            ("device.local", ""),
+        ] {
+            assert!(!host_matches(pattern, host), "{pattern} {host}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_virtual_host_dispatch() {
+        let handler = VirtualHost::new("admin.local", Named("admin"), Named("public"))
+            .host("*.portal.local", Named("portal"));
+
+        for (host, name) in [
+            ("admin.local", "admin"),
+            ("ADMIN.local:80", "admin"),
+            ("login.portal.local", "portal"),
+            ("192.168.4.1", "public"),
+            ("", "public"),
+        ] {
+            for path in ["/", "/ws"] {
+                let mut buf = std::format!(
+                    "GET {path} HTTP/1.1\r\nHost: {host}\r\nUpgrade: websocket\r\n\
+                    Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+                )
+                .into_bytes();
+                let req = Request::parse(&mut buf, usize::MAX, &ServerConfig::default()).unwrap();
+                let mut dst = Vec::new();
+                let mut client = TestClient {
+                    inner: &mut dst,
+                    input: &[],
+                };
+                let resp = Responder::new(&req, &mut client);
+
+                match handler.handle_request(req, resp).await.unwrap() {
+                    Some(websocket) => assert_eq!(
+                        handler.handle_websocket(websocket, &mut []).await,
+                        Err(HandlerError::CustomError(name))
+                    ),
+                    None => assert!(String::from_utf8(dst).unwrap().ends_with(name)),
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_virtual_host_absolute_form() {
+        let handler = VirtualHost::new("admin.local", Named("admin"), Named("public"));
+
+        for (request, name) in [
+            (
+                "GET http://admin.local/ HTTP/1.1\r\nHost: other.local\r\n\r\n",
+                "admin",
+            ),
+            ("GET http://user@admin.local:80/ HTTP/1.1\r\n\r\n", "admin"),
+            (
+                "GET http://other.local/ HTTP/1.1\r\nHost: admin.local\r\n\r\n",
+                "public",
+            ),
+            ("GET / HTTP/1.1\r\nHost: admin.local\r\n\r\n", "admin"),
+        ] {
+            let mut buf = request.as_bytes().to_vec();
+            let req = Request::parse(&mut buf, usize::MAX, &ServerConfig::default()).unwrap();
+            let mut dst = Vec::new();
+            let mut client = TestClient {
+                inner: &mut dst,
+                input: &[],
+            };
+            let resp = Responder::new(&req, &mut client);
+            handler.handle_request(req, resp).await.unwrap();
+            assert!(String::from_utf8(dst).unwrap().ends_with(name), "{request}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_virtual_host_errors() {
+        let server = Server::new(VirtualHost::new(
+            "admin.local",
+            Named("admin"),
+            Named("public"),
+        ));
+
+        for (request, response) in [
+            (
+                "POST /readonly HTTP/1.1\r\nHost: admin.local\r\n\r\n",
+                "HTTP/1.1 405 Method Not Allowed\r\nServer: admin.local\r\n\
+                Allow: GET, HEAD, OPTIONS\r\nContent-Length: 5\r\n\r\nadmin",
+            ),
+            (
+                "OPTIONS /readonly HTTP/1.1\r\nHost: other.local\r\n\r\n",
+                "HTTP/1.1 204 No Content\r\nServer: other.local\r\n\
+                Allow: GET, HEAD, OPTIONS\r\n\r\n",
+            ),
+            (
+                "GET /unfinished HTTP/1.1\r\nHost: admin.local\r\n\r\n",
+                "HTTP/1.1 500 Internal Server Error\r\nConnection: close\r\n\
+                Content-Length: 5\r\n\r\nadmin",
+            ),
+            (
+                "GET /unfinished HTTP/1.1\r\nHost: other.local\r\n\r\n",
+                "HTTP/1.1 500 Internal Server Error\r\nConnection: close\r\n\
+                Content-Length: 6\r\n\r\npublic",
+            ),
+            (
+                "GET / HTTP/1.1\r\nHost: admin.local\r\nContent-Length: x\r\n\r\n",
+                "HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\
+                Content-Length: 6\r\n\r\npublic",
+            ),
+        ] {
+            let mut dst = Vec::new();
+            let mut client = TestClient {
+                inner: &mut dst,
+                input: request.as_bytes(),
+            };
+            let _ = server.serve(&mut client, &mut [0u8; 256]).await;
+            assert_eq!(String::from_utf8(dst).unwrap(), response, "{request}");
+        }
+    }
+}
@@ -26,7 +26,7 @@
 //!         req: Request<'buff>,
 //!         resp: Responder<'buff, 'client, C>,
 //!     ) -> Result<Option<Websocket<'client, C>>, HandlerError> {
-//!         match req.path {
+//!         match req.target.path() {
 //!             "/ws" => {
 //!                 // upgrade and return the websocket, which will then be
 //!                 // used to call handle_websocket
@@ -67,6 +67,14 @@ use sha1::{Digest, Sha1};
 
 const SEC_WEBSOCKET_ACCEPT_MAGIC: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
 
+#[cfg(feature = "json")]
+const OPCODE_TEXT: u8 = 1;
+const OPCODE_BINARY: u8 = 2;
+const OPCODE_CLOSE: u8 = 8;
+
+/// Status code sent in the close frame when the server is shutting down
+pub(crate) const CLOSE_GOING_AWAY: u16 = 1001;
+
 pub(crate) fn sec_websocket_accept_val(key: &str) -> Result<[u8; 28], &'static str> {
     let mut key_hasher = Sha1::new();
     key_hasher.update(key.as_bytes());
@@ -96,12 +104,31 @@ pub enum WebsocketError {
 /// Provides the Websocket protocol over the client connection
 pub struct Websocket<'a, C: Read + Write> {
     conn: &'a mut C,
+    // choices recorded with `Responder::with_route` not yet taken
+    route: usize,
 }
 
 impl<'a, C: Read + Write> Websocket<'a, C> {
     /// Return a new Websocket over the provided cllient connection
     pub fn new(conn: &'a mut C) -> Self {
-        Self { conn }
+        Self { conn, route: 0 }
+    }
+
+    /// Carry the choices recorded by the responder the websocket was upgraded from.
+    pub(crate) fn routed(mut self, route: usize) -> Self {
+        self.route = route;
+        self
+    }
+
+    /// Take the first of the choices recorded with `Responder::with_route` while the request
+    /// that was upgraded to the websocket was dispatched, given the `count` it was recorded with.
+    /// Returns 0 if no choice was recorded.
+    #[doc(hidden)]
+    pub fn take_route(&mut self, count: usize) -> usize {
+        let count = count.max(1);
+        let index = self.route % count;
+        self.route /= count;
+        index
     }
 
     /// Receive a websocket frame from the client writing the payload data into the supplied buffer.
@@ -157,9 +184,36 @@ impl<'a, C: Read + Write> Websocket<'a, C> {
 
     /// Send the provided data bytes to the client after encoding it into a Websocket frame
     pub async fn send(&mut self, data: &mut [u8]) -> Result<(), WebsocketError> {
+        self.send_frame(OPCODE_BINARY, data).await
+    }
+
+    /// Serialize `value` as JSON into `buf` and send it to the client in a text frame.  Available
+    /// with the `json` feature.
+    #[cfg(feature = "json")]
+    pub async fn send_json<T: serde::Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+        buf: &mut [u8],
+    ) -> Result<(), WebsocketError> {
+        let len = serde_json_core::to_slice(value, buf).map_err(|e| match e {
+            serde_json_core::ser::Error::BufferFull => {
+                WebsocketError::Unsupported("json payload exceeds buffer size")
+            }
+            _ => WebsocketError::Unsupported("value cannot be serialized as json"),
+        })?;
+        self.send_frame(OPCODE_TEXT, &buf[..len]).await
+    }
+
+    /// Send a close frame to the client with the provided status code, e.g. 1000 for a normal
+    /// closure, or 1001 when the server is going away.
+    pub async fn close(&mut self, code: u16) -> Result<(), WebsocketError> {
+        self.send_frame(OPCODE_CLOSE, &code.to_be_bytes()).await
+    }
+
+    async fn send_frame(&mut self, opcode: u8, data: &[u8]) -> Result<(), WebsocketError> {
         let header = WebsocketFrame {
             fin: true,
-            opcode: 2,
+            opcode,
             masked: false,
             len: data.len(),
             mask: None,